@@ -0,0 +1,704 @@
+use std::{io, path::PathBuf, time::Duration};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::{
+    prelude::{Backend, Rect},
+    widgets::Paragraph,
+    Frame,
+};
+
+use console_tetris::{
+    clock::FrameClock,
+    game::{GameState, Movement},
+    randomizer::random_seed,
+    replay::{Input, Replay},
+    rules::{GameMode, Rules},
+};
+
+use crate::{
+    auto_repeat::AutoRepeat,
+    controls::{Action, KeyBinding, Keymap},
+    game_handler::Message,
+    graphics::Tetris,
+    high_scores::{self, HighScore, HighScores, MAX_NAME_LENGTH},
+    menu::{Menu, MenuAction},
+    replays::{self, ReplayViewer},
+    saves,
+    settings::Settings,
+};
+
+/// Everything the game loop keeps between frames
+#[derive(Debug)]
+pub struct App {
+    /// The game being played, [`None`] at the main menu
+    pub gamestate: Option<GameState>,
+    pub rules: Rules,
+    /// The seed every game is played with, a random one for each game if [`None`]
+    pub seed: Option<u64>,
+    pub settings: Settings,
+    pub keymap: Keymap,
+    /// The action waiting for a key on the controls screen
+    pub rebinding: Option<Action>,
+    /// The open menus, the last one is drawn on top and receives input
+    pub menus: Vec<Menu>,
+    /// Set once the player has asked to quit
+    pub quit: bool,
+    /// Whether the terminal reports key releases
+    ///
+    /// Without them movement keys repeat at the terminal's key repeat rate
+    /// and soft drop moves one row per press
+    pub key_releases: bool,
+    pub auto_repeat: AutoRepeat,
+    /// The frames the game is run on
+    pub frames: FrameClock,
+    /// The replay being watched instead of playing
+    pub viewer: Option<ReplayViewer>,
+    /// Where replays of played games are saved, [`None`] if there is no data directory
+    pub replay_dir: Option<PathBuf>,
+    /// The game saved when last quitting, until it is continued
+    pub saved_game: Option<GameState>,
+    /// Whether the game being played was continued from the save
+    ///
+    /// The save is kept until the game ends or is left, in case the program doesn't
+    /// get to save it again
+    pub resumed: bool,
+    pub high_scores: HighScores,
+    /// The name the last high score was saved under
+    pub player_name: String,
+    /// The result waiting for its name to be typed before going on the high score table
+    pub naming: Option<HighScore>,
+    /// The mode the high score table shows
+    pub high_score_mode: GameMode,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            gamestate: None,
+            rules: Rules::default(),
+            seed: None,
+            settings: Settings::default(),
+            keymap: Keymap::default(),
+            rebinding: None,
+            menus: vec![Menu::main(false)],
+            quit: false,
+            key_releases: false,
+            auto_repeat: AutoRepeat::default(),
+            frames: FrameClock::default(),
+            viewer: None,
+            replay_dir: replays::dir(),
+            saved_game: None,
+            resumed: false,
+            high_scores: HighScores::default(),
+            player_name: high_scores::default_name(),
+            naming: None,
+            high_score_mode: GameMode::default(),
+        }
+    }
+}
+
+impl App {
+    /// Apply a [`Message`] from the io handler
+    ///
+    /// Terminal related messages are left to the game loop
+    pub fn handle_message(&mut self, message: Message) {
+        self.apply_message(message);
+        self.show_game_over();
+    }
+
+    fn apply_message(&mut self, message: Message) {
+        if let Some(viewer) = self.viewer.as_mut() {
+            match message {
+                Message::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => self.quit = true,
+                    _ => viewer.handle_key(key),
+                },
+                Message::QuitGame => self.quit = true,
+                Message::Pause => viewer.paused = true,
+                _ => (),
+            }
+            return;
+        }
+
+        if let Message::Key(key) = message {
+            if self.naming.is_some() {
+                if key.kind == KeyEventKind::Press {
+                    self.edit_name(key);
+                }
+                return;
+            }
+
+            if let Some(action) = self.rebinding {
+                if key.kind == KeyEventKind::Press {
+                    self.rebind(action, key);
+                }
+                return;
+            }
+
+            match self.menus.last_mut() {
+                Some(menu) => {
+                    if key.kind == KeyEventKind::Press {
+                        if let Some(action) = menu.handle_key(key) {
+                            self.handle_menu_action(action);
+                        }
+                    }
+                }
+                None => self.handle_key(key),
+            }
+            return;
+        }
+
+        match message {
+            Message::QuitGame => self.quit = true,
+            Message::ToggleGhost => self.settings.ghost_piece = !self.settings.ghost_piece,
+            _ => (),
+        }
+
+        // the remaining messages only apply to a game in progress
+        let Some(gamestate) = self.gamestate.as_mut() else {
+            return;
+        };
+        if gamestate.game_over.is_some() || !self.menus.is_empty() {
+            return;
+        }
+
+        match message {
+            Message::Move(movement) => {
+                gamestate.input(Input::Move(movement));
+            }
+            Message::NewTetrimino => {
+                gamestate.input(Input::NewTetrimino);
+            }
+            Message::Hold => {
+                gamestate.input(Input::Hold);
+            }
+            Message::Pause => {
+                self.auto_repeat.clear();
+                gamestate.pause();
+                self.menus.push(Menu::pause());
+            }
+            _ => (),
+        }
+    }
+
+    /// Apply a key event during play
+    ///
+    /// When the terminal reports key releases, left, right and soft drop are
+    /// handled while held, instead of once per press
+    fn handle_key(&mut self, key: KeyEvent) {
+        let Some(message) = self.keymap.message(key) else {
+            return;
+        };
+
+        let held_movement = match message {
+            Message::Move(movement @ (Movement::Left | Movement::Right | Movement::Down)) => {
+                Some(movement)
+            }
+            _ => None,
+        };
+
+        match (held_movement, key.kind) {
+            (Some(movement), kind) if self.key_releases => {
+                let Some(gamestate) = self.gamestate.as_mut() else {
+                    return;
+                };
+                if gamestate.game_over.is_some() {
+                    return;
+                }
+
+                let das = self.settings.das;
+                match (movement, kind) {
+                    (Movement::Down, KeyEventKind::Press) => {
+                        gamestate.input(Input::SoftDrop(Some(self.settings.soft_drop_factor)));
+                    }
+                    (Movement::Down, KeyEventKind::Release) => {
+                        gamestate.input(Input::SoftDrop(None));
+                    }
+                    (_, KeyEventKind::Press) => self.auto_repeat.press(movement, das, gamestate),
+                    (_, KeyEventKind::Release) => {
+                        self.auto_repeat.release(movement, das, gamestate)
+                    }
+                    (_, KeyEventKind::Repeat) => (),
+                }
+            }
+            (_, KeyEventKind::Press) => self.apply_message(message),
+            _ => (),
+        }
+    }
+
+    /// Bind `key` to `action` on the controls screen
+    ///
+    /// Esc cancels and Backspace removes every key from the action
+    fn rebind(&mut self, action: Action, key: KeyEvent) {
+        self.rebinding = None;
+
+        let binding = KeyBinding::from(key);
+        match key.code {
+            KeyCode::Esc => {
+                self.refresh_controls_menu(None);
+                return;
+            }
+            KeyCode::Backspace => self.keymap.clear(action),
+            _ if binding.nameable() => self.keymap.bind(action, binding),
+            _ => (),
+        }
+
+        self.save_keymap();
+    }
+
+    /// Type the name of a new high score
+    ///
+    /// Enter saves it and Esc leaves the result off the table
+    fn edit_name(&mut self, key: KeyEvent) {
+        let Some(high_score) = self.naming.as_mut() else {
+            return;
+        };
+
+        match key.code {
+            KeyCode::Enter => return self.handle_menu_action(MenuAction::SaveHighScore),
+            KeyCode::Esc => {
+                self.naming = None;
+                self.menus.pop();
+                return;
+            }
+            KeyCode::Backspace => {
+                high_score.name.pop();
+            }
+            KeyCode::Char(c)
+                if !c.is_control()
+                    && !key
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                if high_score.name.chars().count() < MAX_NAME_LENGTH {
+                    high_score.name.push(c);
+                }
+            }
+            _ => return,
+        }
+
+        let Some(mode) = self
+            .gamestate
+            .as_ref()
+            .map(|gamestate| gamestate.rules.mode)
+        else {
+            return;
+        };
+        if let Some(rank) = self.high_scores.rank(mode, high_score) {
+            self.menus.pop();
+            self.menus
+                .push(Menu::new_high_score(mode, rank, &high_score.name));
+        }
+    }
+
+    fn handle_menu_action(&mut self, action: MenuAction) {
+        match action {
+            MenuAction::Play => self.menus.push(Menu::modes()),
+            MenuAction::Continue => {
+                let Some(mut gamestate) = self.saved_game.take() else {
+                    return;
+                };
+                self.resumed = true;
+                gamestate.pause();
+                self.rules = gamestate.rules.to_owned();
+                self.gamestate = Some(gamestate);
+                self.auto_repeat.clear();
+                self.menus = vec![Menu::pause()];
+            }
+            MenuAction::Start(mode) => {
+                self.rules.mode = mode;
+                self.handle_menu_action(MenuAction::Restart);
+            }
+            MenuAction::Restart => {
+                self.abandon_game();
+                self.menus.clear();
+                self.auto_repeat.clear();
+                // the settings may have changed since the last game started
+                self.rules = self.settings.rules(self.rules.mode);
+                let seed = self.seed.unwrap_or_else(random_seed);
+                self.gamestate = Some(GameState::new(self.rules.to_owned(), seed));
+            }
+            MenuAction::Resume => {
+                self.menus.pop();
+                if let Some(gamestate) = self.gamestate.as_mut() {
+                    gamestate.resume();
+                }
+            }
+            MenuAction::Settings => self.menus.push(Menu::settings(&self.settings)),
+            MenuAction::HighScores => {
+                self.high_score_mode = match self.gamestate.as_ref() {
+                    Some(gamestate) => gamestate.rules.mode,
+                    None => self.rules.mode,
+                };
+                self.menus.push(Menu::high_scores(
+                    &self.high_scores,
+                    self.high_score_mode,
+                    None,
+                    None,
+                ));
+            }
+            MenuAction::CycleHighScoreMode => {
+                self.high_score_mode = self.high_score_mode.next();
+                self.refresh_high_scores_menu();
+            }
+            MenuAction::SaveHighScore => {
+                let (Some(mut high_score), Some(gamestate)) =
+                    (self.naming.take(), self.gamestate.as_ref())
+                else {
+                    return;
+                };
+                let mode = gamestate.rules.mode;
+
+                high_score.name = high_score.name.trim().to_owned();
+                if high_score.name.is_empty() {
+                    high_score.name = high_scores::default_name();
+                }
+                self.player_name = high_score.name.to_owned();
+
+                let rank = self.high_scores.insert(mode, high_score);
+                let error = self.high_scores.save().err().map(|err| err.to_string());
+                self.high_score_mode = mode;
+                self.menus.pop();
+                self.menus
+                    .push(Menu::high_scores(&self.high_scores, mode, rank, error));
+            }
+            MenuAction::MainMenu => {
+                self.abandon_game();
+                self.gamestate = None;
+                self.menus = vec![Menu::main(self.saved_game.is_some())];
+            }
+            MenuAction::Quit => self.quit = true,
+            MenuAction::ToggleGhost => {
+                self.settings.ghost_piece = !self.settings.ghost_piece;
+                self.refresh_settings_menu();
+            }
+            MenuAction::CycleDas => {
+                self.settings.cycle_das();
+                self.refresh_settings_menu();
+            }
+            MenuAction::CycleArr => {
+                self.settings.cycle_arr();
+                self.refresh_settings_menu();
+            }
+            MenuAction::CycleSoftDrop => {
+                self.settings.cycle_soft_drop_factor();
+                self.refresh_settings_menu();
+            }
+            MenuAction::CycleNextPreviews => {
+                self.settings.cycle_next_previews();
+                self.refresh_settings_menu();
+            }
+            MenuAction::ToggleNextQueueSide => {
+                self.settings.next_queue_side = self.settings.next_queue_side.flipped();
+                self.refresh_settings_menu();
+            }
+            MenuAction::CycleRandomizer => {
+                self.settings.cycle_randomizer();
+                self.refresh_settings_menu();
+            }
+            MenuAction::CycleRotationSystem => {
+                self.settings.cycle_rotation_system();
+                self.refresh_settings_menu();
+            }
+            MenuAction::Controls => self.menus.push(Menu::controls(&self.keymap, None, None)),
+            MenuAction::CyclePreset => {
+                self.keymap.set_preset(self.keymap.preset.next());
+                self.save_keymap();
+            }
+            MenuAction::Rebind(action) => {
+                self.rebinding = Some(action);
+                self.refresh_controls_menu(None);
+            }
+            MenuAction::Back => {
+                self.menus.pop();
+            }
+        }
+    }
+
+    /// The game being played, if it can still be continued
+    pub fn unfinished_game(&self) -> Option<&GameState> {
+        self.gamestate
+            .as_ref()
+            .filter(|gamestate| gamestate.game_over.is_none())
+    }
+
+    /// Save the replay of a game thrown away before it ended
+    ///
+    /// Finished games are saved when they end, quit games once they are continued and finished
+    fn abandon_game(&mut self) {
+        self.discard_save();
+        let Some(gamestate) = self.unfinished_game() else {
+            return;
+        };
+        if gamestate.frame > 0 {
+            // the game is being left, so there is nowhere to show an error
+            let _ = self.save_replay(gamestate);
+        }
+    }
+
+    /// Delete the save of a continued game once it is over or left
+    ///
+    /// A game quit before it ends is saved again when quitting
+    fn discard_save(&mut self) {
+        if self.resumed {
+            self.resumed = false;
+            // the save is only there to be continued, so there is nowhere to show an error
+            let _ = saves::remove();
+        }
+    }
+
+    /// Save the replay of `gamestate` in the replay directory
+    ///
+    /// Returns the path it was saved to
+    fn save_replay(&self, gamestate: &GameState) -> Result<PathBuf, io::Error> {
+        let Some(dir) = self.replay_dir.as_deref() else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no data directory"));
+        };
+        replays::save(dir, &Replay::from(gamestate))
+    }
+
+    /// Rebuild the settings menu so it shows the current values
+    fn refresh_settings_menu(&mut self) {
+        if let Some(menu) = self.menus.pop() {
+            let selected = menu.selected();
+            self.menus
+                .push(Menu::settings(&self.settings).with_selected(selected));
+        }
+    }
+
+    /// Rebuild the high score menu so it shows the table of the chosen mode
+    fn refresh_high_scores_menu(&mut self) {
+        if let Some(menu) = self.menus.pop() {
+            let selected = menu.selected();
+            self.menus.push(
+                Menu::high_scores(&self.high_scores, self.high_score_mode, None, None)
+                    .with_selected(selected),
+            );
+        }
+    }
+
+    /// Rebuild the controls menu so it shows the current keymap
+    fn refresh_controls_menu(&mut self, error: Option<String>) {
+        if let Some(menu) = self.menus.pop() {
+            let selected = menu.selected();
+            self.menus
+                .push(Menu::controls(&self.keymap, self.rebinding, error).with_selected(selected));
+        }
+    }
+
+    /// Save the changed keymap for the next launch and show it
+    fn save_keymap(&mut self) {
+        let error = self.keymap.save().err().map(|err| err.to_string());
+        self.refresh_controls_menu(error);
+    }
+
+    /// How long until [`App::tick`] has work to do, see [`GameState::next_deadline`]
+    pub fn next_deadline(&self) -> Option<Duration> {
+        if let Some(viewer) = self.viewer.as_ref() {
+            return viewer
+                .next_deadline()
+                .map(|frames| self.frames.time_until(frames));
+        }
+
+        let gamestate = self.gamestate.as_ref()?;
+        let auto_repeat = self
+            .auto_repeat
+            .next_deadline(self.settings.arr)
+            .filter(|_| self.menus.is_empty());
+
+        let deadline = match (gamestate.next_deadline(), auto_repeat) {
+            (Some(gamestate), Some(auto_repeat)) => gamestate.min(auto_repeat),
+            (Some(deadline), None) | (None, Some(deadline)) => deadline,
+            (None, None) => return None,
+        };
+
+        // the game only runs while it is being played, so its frames line up with ours
+        Some(
+            self.frames
+                .time_until(deadline.saturating_sub(gamestate.frame)),
+        )
+    }
+
+    /// Run every frame that came due, showing the game over screen once the game ends
+    pub fn tick(&mut self) {
+        let frames = self.frames.catch_up();
+        if let Some(viewer) = self.viewer.as_mut() {
+            viewer.advance(frames);
+            return;
+        }
+
+        let Some(gamestate) = self.gamestate.as_mut() else {
+            return;
+        };
+
+        for _ in 0..frames {
+            if !gamestate.step() {
+                break;
+            }
+
+            // shift after gravity so a newly spawned Tetrimino is shifted right away
+            if self.menus.is_empty() {
+                self.auto_repeat.tick(self.settings.arr, gamestate);
+            }
+        }
+        self.show_game_over();
+    }
+
+    /// Show the game over screen once the game has ended, along with the name prompt
+    /// if the result made the high score table
+    ///
+    /// Games end on a frame as well as on an input, such as a hard drop that blocks out
+    fn show_game_over(&mut self) {
+        let over = self
+            .gamestate
+            .as_ref()
+            .is_some_and(|gamestate| gamestate.game_over.is_some());
+        if !over || !self.menus.is_empty() {
+            return;
+        }
+
+        self.auto_repeat.clear();
+        self.discard_save();
+        let Some(gamestate) = self.gamestate.as_ref() else {
+            return;
+        };
+        let replay = self.save_replay(gamestate);
+        self.menus.push(Menu::game_over(gamestate, replay));
+
+        if !high_scores::ranked(gamestate) {
+            return;
+        }
+        let mode = gamestate.rules.mode;
+        let high_score = HighScore::new(&self.player_name, gamestate);
+        if let Some(rank) = self.high_scores.rank(mode, &high_score) {
+            self.menus
+                .push(Menu::new_high_score(mode, rank, &high_score.name));
+            self.naming = Some(high_score);
+        }
+    }
+
+    pub fn draw(&mut self, f: &mut Frame<impl Backend>) {
+        if let Some(viewer) = self.viewer.as_mut() {
+            // the status goes on the last line, below the game
+            let area = f.size();
+            let status = Rect::new(area.x, area.bottom().saturating_sub(1), area.width, 1);
+            let area = Rect::new(
+                area.x,
+                area.y,
+                area.width,
+                area.height.saturating_sub(status.height),
+            );
+
+            f.render_stateful_widget(
+                Tetris {
+                    settings: &self.settings,
+                },
+                area,
+                &mut viewer.playback.gamestate,
+            );
+            // the key hints are left out when they don't fit
+            let mut line = viewer.status();
+            let hints = "  Space pause  , . step  ←→ seek  ↑↓ speed  q quit";
+            if line.chars().count() + hints.chars().count() <= status.width.into() {
+                line.push_str(hints);
+            }
+            f.render_widget(Paragraph::new(line), status);
+            return;
+        }
+
+        if let Some(gamestate) = self.gamestate.as_mut() {
+            f.render_stateful_widget(
+                Tetris {
+                    settings: &self.settings,
+                },
+                f.size(),
+                gamestate,
+            );
+        }
+
+        if let Some(menu) = self.menus.last() {
+            f.render_widget(menu, f.size());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use console_tetris::{clock::ManualClock, randomizer::RandomizerKind};
+
+    use super::*;
+
+    /// An app whose frames only advance when the returned clock is moved
+    ///
+    /// Replays are saved in a temporary directory named after `test`,
+    /// removed with [`remove_replays`]
+    fn manual_app(test: &str) -> (App, ManualClock) {
+        let clock = ManualClock::default();
+        let app = App {
+            frames: FrameClock::new(clock.clone()),
+            replay_dir: Some(
+                env::temp_dir().join(format!("console_tetris-{test}-{}", process::id())),
+            ),
+            ..App::default()
+        };
+        (app, clock)
+    }
+
+    /// Remove the replays `app` saved, checking the game's replay was among them
+    fn remove_replays(app: App) {
+        let dir = app
+            .replay_dir
+            .expect("replays are saved in a temporary directory");
+        assert_eq!(fs::read_dir(&dir).expect("a replay was saved").count(), 1);
+        fs::remove_dir_all(dir).expect("temporary directory can be removed");
+    }
+
+    /// Start a Marathon game and stack hard drops until one blocks out,
+    /// without any frames passing
+    fn block_out(app: &mut App) {
+        app.handle_menu_action(MenuAction::Start(GameMode::Marathon));
+        assert!(app.menus.is_empty());
+
+        for _ in 0..100 {
+            app.handle_message(Message::Move(Movement::Drop));
+            if !app.menus.is_empty() {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn game_over_from_an_input_shows_the_game_over_menu() {
+        let (mut app, _clock) = manual_app("game-over");
+        block_out(&mut app);
+
+        let gamestate = app.gamestate.as_ref().expect("the game is kept");
+        assert!(gamestate.game_over.is_some());
+        assert_eq!(gamestate.frame, 0);
+        // the hard drops scored, so the name prompt opens over the game over menu
+        assert!(app.naming.is_some());
+        assert_eq!(app.menus.len(), 2);
+        assert_eq!(
+            app.menus[0].handle_key(KeyEvent::from(KeyCode::Esc)),
+            Some(MenuAction::MainMenu)
+        );
+        remove_replays(app);
+    }
+
+    #[test]
+    fn games_with_custom_rules_are_not_ranked() {
+        let (mut app, _clock) = manual_app("custom-rules");
+        app.settings.randomizer = Some(RandomizerKind::PureRandom);
+        block_out(&mut app);
+
+        let gamestate = app.gamestate.as_ref().expect("the game is kept");
+        assert!(gamestate.game_over.is_some());
+        assert!(gamestate.scoring.score > 0);
+        // straight to the game over menu, without asking for a name
+        assert!(app.naming.is_none());
+        assert_eq!(app.menus.len(), 1);
+        remove_replays(app);
+    }
+}
@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+
+use console_tetris::randomizer::MAX_SEED;
+
+pub const USAGE: &str = "\
+Usage: console_tetris [options]
+
+Options:
+    --seed <number>    Play every game with the same Tetrimino sequence
+    --replay <file>    Watch a saved replay
+    -h, --help         Show this message
+
+Replays of every game are saved in console_tetris/replays in the data directory.
+A game quit before it ends is saved as console_tetris/save.toml in the data
+directory, to be continued from the menu.";
+
+/// Options given on the command line
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Args {
+    /// Play every game with this seed instead of a random one
+    pub seed: Option<u64>,
+    /// Watch this replay instead of playing
+    pub replay: Option<PathBuf>,
+    /// Show the usage instead of playing
+    pub help: bool,
+}
+
+impl Args {
+    /// Parse `args`, not including the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => {
+                    let seed = args.next().ok_or("--seed needs a number")?;
+                    parsed.seed = Some(
+                        seed.parse()
+                            .ok()
+                            .filter(|seed| *seed <= MAX_SEED)
+                            .ok_or_else(|| {
+                                format!("`{seed}` is not a valid seed, from 0 to {MAX_SEED}")
+                            })?,
+                    );
+                }
+                "--replay" => {
+                    let path = args.next().ok_or("--replay needs a file")?;
+                    parsed.replay = Some(PathBuf::from(path));
+                }
+                "-h" | "--help" => parsed.help = true,
+                _ => return Err(format!("unknown option `{arg}`")),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn seed_must_fit_a_save() {
+        assert_eq!(
+            parse(&["--seed", &MAX_SEED.to_string()]).map(|args| args.seed),
+            Ok(Some(MAX_SEED))
+        );
+        assert!(parse(&["--seed", &(MAX_SEED + 1).to_string()]).is_err());
+        assert!(parse(&["--seed", "18446744073709551615"]).is_err());
+        assert!(parse(&["--seed", "-1"]).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use std::time::Duration;
+
+use console_tetris::{
+    clock::frames,
+    game::{GameState, Movement},
+    replay::Input,
+};
+
+/// Delayed Auto Shift for the held left and right keys
+///
+/// Pressing a direction shifts the Tetrimino once. Holding it for the DAS
+/// starts repeating the shift every ARR until the key is released.
+/// Only usable on terminals that report key releases.
+///
+/// Runs on the frames of the [`GameState`], so shifts land on whole frames
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AutoRepeat {
+    /// The held directions, the most recently pressed one is last and wins
+    held: Vec<Movement>,
+    /// The frame the held direction shifts next on
+    next_shift: Option<u64>,
+    /// Whether the DAS has run out and the shift is repeating
+    charged: bool,
+}
+
+impl AutoRepeat {
+    /// Shift once in `movement` and start charging the DAS
+    ///
+    /// Presses of the direction already being shifted are repeats and ignored
+    pub fn press(&mut self, movement: Movement, das: Duration, gamestate: &mut GameState) {
+        if self.held.last() == Some(&movement) {
+            return;
+        }
+
+        self.held.retain(|held| *held != movement);
+        self.held.push(movement);
+        self.next_shift = Some(gamestate.frame + frames(das));
+        self.charged = false;
+
+        gamestate.input(Input::Move(movement));
+    }
+
+    /// Stop shifting in `movement`
+    ///
+    /// If the other direction is still held, it takes over after a fresh DAS
+    pub fn release(&mut self, movement: Movement, das: Duration, gamestate: &GameState) {
+        let active = self.held.last() == Some(&movement);
+        self.held.retain(|held| *held != movement);
+
+        if active {
+            self.next_shift = self.held.last().map(|_| gamestate.frame + frames(das));
+            self.charged = false;
+        }
+    }
+
+    /// Forget the held keys
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// The next frame [`AutoRepeat::tick`] has work to do on
+    ///
+    /// With an ARR of zero the Tetrimino sits at the wall once charged,
+    /// so there is nothing to wait for
+    pub fn next_deadline(&self, arr: Duration) -> Option<u64> {
+        if self.charged && arr.is_zero() {
+            return None;
+        }
+        self.next_shift
+    }
+
+    /// Shift the Tetrimino for every repeat that is due
+    pub fn tick(&mut self, arr: Duration, gamestate: &mut GameState) {
+        let (Some(&movement), Some(next_shift)) = (self.held.last(), self.next_shift.as_mut())
+        else {
+            return;
+        };
+
+        let now = gamestate.frame;
+        if now < *next_shift {
+            return;
+        }
+        self.charged = true;
+
+        if arr.is_zero() {
+            while gamestate.input(Input::Move(movement)) {}
+            return;
+        }
+
+        // an ARR shorter than a frame still shifts once per frame
+        while *next_shift <= now {
+            gamestate.input(Input::Move(movement));
+            *next_shift += frames(arr).max(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use console_tetris::{
+        clock::duration,
+        rotation::RotationSystemKind,
+        rules::Rules,
+        tetramino::{Tetrimino, TetriminoType},
+    };
+
+    use super::*;
+
+    const DAS: u64 = 10;
+    const ARR: u64 = 2;
+
+    /// A game with a T against the left wall, free to shift right 7 times
+    fn t_at_the_left_wall() -> GameState {
+        let mut gamestate = GameState::new(Rules::default(), 1);
+        gamestate
+            .game
+            .spawn(Tetrimino::new(TetriminoType::T, RotationSystemKind::Srs))
+            .unwrap();
+        while gamestate.game.apply_movement(Movement::Left) {}
+        gamestate
+    }
+
+    /// Run `count` frames the way the game loop does, shifting after gravity
+    fn run(auto_repeat: &mut AutoRepeat, arr: u64, gamestate: &mut GameState, count: u64) {
+        for _ in 0..count {
+            assert!(gamestate.step());
+            auto_repeat.tick(duration(arr), gamestate);
+        }
+    }
+
+    /// The frames the Tetrimino was shifted on
+    fn shifts(gamestate: &GameState) -> Vec<u64> {
+        gamestate
+            .inputs
+            .iter()
+            .filter(|(_, input)| matches!(input, Input::Move(Movement::Left | Movement::Right)))
+            .map(|(frame, _)| *frame)
+            .collect()
+    }
+
+    #[test]
+    fn press_shifts_once_then_repeats_after_the_das() {
+        let mut gamestate = t_at_the_left_wall();
+        let mut auto_repeat = AutoRepeat::default();
+
+        auto_repeat.press(Movement::Right, duration(DAS), &mut gamestate);
+        assert_eq!(shifts(&gamestate), vec![0]);
+        assert_eq!(auto_repeat.next_deadline(duration(ARR)), Some(DAS));
+
+        // a repeated press from the terminal doesn't shift or restart the DAS
+        run(&mut auto_repeat, ARR, &mut gamestate, 4);
+        auto_repeat.press(Movement::Right, duration(DAS), &mut gamestate);
+        run(&mut auto_repeat, ARR, &mut gamestate, DAS - 5);
+        assert_eq!(shifts(&gamestate), vec![0]);
+
+        // one shift on the frame the DAS runs out, then one every ARR
+        run(&mut auto_repeat, ARR, &mut gamestate, 1 + 3 * ARR);
+        assert_eq!(
+            shifts(&gamestate),
+            vec![0, DAS, DAS + ARR, DAS + 2 * ARR, DAS + 3 * ARR]
+        );
+
+        // until the wall
+        run(&mut auto_repeat, ARR, &mut gamestate, 10 * ARR);
+        assert_eq!(shifts(&gamestate).len(), 7);
+    }
+
+    #[test]
+    fn zero_arr_shifts_to_the_wall() {
+        let mut gamestate = t_at_the_left_wall();
+        let mut auto_repeat = AutoRepeat::default();
+
+        auto_repeat.press(Movement::Right, duration(DAS), &mut gamestate);
+        run(&mut auto_repeat, 0, &mut gamestate, DAS);
+        assert_eq!(shifts(&gamestate), vec![0, DAS, DAS, DAS, DAS, DAS, DAS]);
+        assert!(!gamestate.game.apply_movement(Movement::Right));
+        assert_eq!(auto_repeat.next_deadline(Duration::ZERO), None);
+    }
+
+    #[test]
+    fn releasing_hands_over_to_the_other_direction_after_a_fresh_das() {
+        let mut gamestate = t_at_the_left_wall();
+        let mut auto_repeat = AutoRepeat::default();
+
+        auto_repeat.press(Movement::Right, duration(DAS), &mut gamestate);
+        run(&mut auto_repeat, ARR, &mut gamestate, DAS + ARR);
+        assert_eq!(shifts(&gamestate), vec![0, DAS, DAS + ARR]);
+
+        // the newer press wins while both are held
+        let pressed = gamestate.frame;
+        auto_repeat.press(Movement::Left, duration(DAS), &mut gamestate);
+        run(&mut auto_repeat, ARR, &mut gamestate, 3);
+        auto_repeat.release(Movement::Left, duration(DAS), &gamestate);
+
+        // right shifts again only once a new DAS has passed
+        let released = gamestate.frame;
+        run(&mut auto_repeat, ARR, &mut gamestate, DAS - 1);
+        assert_eq!(shifts(&gamestate), vec![0, DAS, DAS + ARR, pressed]);
+        run(&mut auto_repeat, ARR, &mut gamestate, 1);
+        assert_eq!(
+            shifts(&gamestate),
+            vec![0, DAS, DAS + ARR, pressed, released + DAS]
+        );
+
+        // nothing shifts once both are released
+        auto_repeat.release(Movement::Right, duration(DAS), &gamestate);
+        assert_eq!(auto_repeat.next_deadline(duration(ARR)), None);
+        run(&mut auto_repeat, ARR, &mut gamestate, 2 * DAS);
+        assert_eq!(shifts(&gamestate).len(), 5);
+    }
+}
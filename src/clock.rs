@@ -0,0 +1,186 @@
+use std::{
+    cell::Cell,
+    fmt,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// How many frames the game advances every second
+pub const FRAME_RATE: u32 = 60;
+
+/// The time between two frames
+pub const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / FRAME_RATE as u64);
+
+/// The number of frames closest to `duration`
+pub fn frames(duration: Duration) -> u64 {
+    (duration.as_secs_f64() * FRAME_RATE as f64).round() as u64
+}
+
+/// The time `frames` frames take, rounded up to the nanosecond
+pub fn duration(frames: u64) -> Duration {
+    let nanos = (frames as u128 * 1_000_000_000).div_ceil(FRAME_RATE as u128);
+    Duration::from_nanos(nanos as u64)
+}
+
+/// A source of time for running a game in real time
+pub trait Clock: fmt::Debug {
+    /// The time passed since some fixed point
+    fn now(&self) -> Duration;
+}
+
+/// The system's monotonic clock, starting when it is created
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// A clock that only moves when it is told to
+///
+/// Clones share the same time, so a test can keep one to move the clock
+/// after handing the other to a [`FrameClock`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManualClock {
+    now: Rc<Cell<Duration>>,
+}
+
+impl ManualClock {
+    /// Move the clock forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+/// Splits the time of a [`Clock`] into frames
+///
+/// Frame 0 is when the frame clock is created, frame `n` is due
+/// `n` [`FRAME_TIME`]s later
+#[derive(Debug)]
+pub struct FrameClock {
+    clock: Box<dyn Clock>,
+    /// The time of frame 0
+    start: Duration,
+    /// The last frame that came due
+    frame: u64,
+}
+
+impl FrameClock {
+    pub fn new(clock: impl Clock + 'static) -> Self {
+        Self {
+            start: clock.now(),
+            clock: Box::new(clock),
+            frame: 0,
+        }
+    }
+
+    /// The last frame that came due
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Move up to the current frame
+    ///
+    /// Returns how many frames came due since the last call
+    pub fn catch_up(&mut self) -> u64 {
+        let now = frames_floor(self.clock.now().saturating_sub(self.start));
+        let due = now.saturating_sub(self.frame);
+        self.frame += due;
+        due
+    }
+
+    /// How long until the frame `frames` after the last due one
+    pub fn time_until(&self, frames: u64) -> Duration {
+        (self.start + duration(self.frame + frames)).saturating_sub(self.clock.now())
+    }
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self::new(SystemClock::default())
+    }
+}
+
+/// The number of whole frames in `duration`
+fn frames_floor(duration: Duration) -> u64 {
+    (duration.as_nanos() * FRAME_RATE as u128 / 1_000_000_000) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A frame clock on a manual clock, and the manual clock to move it with
+    fn manual_frame_clock() -> (FrameClock, ManualClock) {
+        let clock = ManualClock::default();
+        clock.advance(Duration::from_secs(3));
+        (FrameClock::new(clock.clone()), clock)
+    }
+
+    #[test]
+    fn no_frame_before_a_frame_time() {
+        let (mut frames, clock) = manual_frame_clock();
+        assert_eq!(frames.catch_up(), 0);
+        assert_eq!(frames.time_until(1), duration(1));
+
+        clock.advance(duration(1) - Duration::from_nanos(1));
+        assert_eq!(frames.catch_up(), 0);
+        assert_eq!(frames.time_until(1), Duration::from_nanos(1));
+
+        clock.advance(Duration::from_nanos(1));
+        assert_eq!(frames.catch_up(), 1);
+        assert_eq!(frames.frame(), 1);
+    }
+
+    #[test]
+    fn catch_up_after_a_long_gap() {
+        let (mut frames, clock) = manual_frame_clock();
+
+        clock.advance(Duration::from_millis(10_008));
+        assert_eq!(frames.catch_up(), 600);
+        assert_eq!(frames.catch_up(), 0);
+        assert_eq!(frames.frame(), 600);
+        assert_eq!(
+            frames.time_until(1),
+            duration(601) - Duration::from_millis(10_008)
+        );
+    }
+
+    #[test]
+    fn no_drift_over_many_steps() {
+        let (mut frames, clock) = manual_frame_clock();
+
+        // sleeping until each frame lands exactly on it
+        for _ in 0..FRAME_RATE * 100 {
+            clock.advance(frames.time_until(1));
+            assert_eq!(frames.catch_up(), 1);
+        }
+        assert_eq!(frames.frame(), FRAME_RATE as u64 * 100);
+        assert_eq!(clock.now(), Duration::from_secs(103));
+
+        // steps that don't line up with frames still add up to the right count
+        for _ in 0..10_000 {
+            clock.advance(Duration::from_millis(10));
+            frames.catch_up();
+        }
+        assert_eq!(frames.frame(), FRAME_RATE as u64 * 200);
+    }
+}
@@ -0,0 +1,536 @@
+use std::{collections::BTreeMap, fmt, fs, io, path::PathBuf, str::FromStr};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+use console_tetris::{game::Movement, rotation::RotationDirection};
+
+use crate::game_handler::Message;
+
+/// Something a key can be bound to during play
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateClockwise,
+    RotateCounterclockwise,
+    #[serde(rename = "rotate_180")]
+    Rotate180,
+    Hold,
+    Pause,
+    ToggleGhost,
+    NewTetrimino,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 12] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::RotateClockwise,
+        Action::RotateCounterclockwise,
+        Action::Rotate180,
+        Action::Hold,
+        Action::Pause,
+        Action::ToggleGhost,
+        Action::NewTetrimino,
+        Action::Quit,
+    ];
+
+    /// The [`Message`] sent when the action's key is pressed
+    pub fn message(self) -> Message {
+        match self {
+            Action::MoveLeft => Message::Move(Movement::Left),
+            Action::MoveRight => Message::Move(Movement::Right),
+            Action::SoftDrop => Message::Move(Movement::Down),
+            Action::HardDrop => Message::Move(Movement::Drop),
+            Action::RotateClockwise => {
+                Message::Move(Movement::Rotate(RotationDirection::Clockwise))
+            }
+            Action::RotateCounterclockwise => {
+                Message::Move(Movement::Rotate(RotationDirection::Counterclockwise))
+            }
+            Action::Rotate180 => Message::Move(Movement::Rotate(RotationDirection::Half)),
+            Action::Hold => Message::Hold,
+            Action::Pause => Message::Pause,
+            Action::ToggleGhost => Message::ToggleGhost,
+            Action::NewTetrimino => Message::NewTetrimino,
+            Action::Quit => Message::QuitGame,
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::SoftDrop => "Soft drop",
+            Action::HardDrop => "Hard drop",
+            Action::RotateClockwise => "Rotate right",
+            Action::RotateCounterclockwise => "Rotate left",
+            Action::Rotate180 => "Rotate 180",
+            Action::Hold => "Hold",
+            Action::Pause => "Pause",
+            Action::ToggleGhost => "Ghost piece",
+            Action::NewTetrimino => "New Tetrimino",
+            Action::Quit => "Quit",
+        })
+    }
+}
+
+/// A key together with the modifiers held with it
+///
+/// Written as the modifiers and key joined by `+`, like `ctrl+left` or `shift+c`.
+/// Shift is part of the character for character keys, so `shift+c` and `C` are the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers =
+            modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        if let KeyCode::Char(_) = code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+
+        Self { code, modifiers }
+    }
+
+    /// Whether the key has a name it can be saved as
+    pub fn nameable(&self) -> bool {
+        self.to_string().parse() == Ok(*self)
+    }
+}
+
+impl From<KeyCode> for KeyBinding {
+    fn from(code: KeyCode) -> Self {
+        Self::new(code, KeyModifiers::NONE)
+    }
+}
+
+impl From<KeyEvent> for KeyBinding {
+    fn from(key: KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl"),
+            (KeyModifiers::ALT, "alt"),
+            (KeyModifiers::SHIFT, "shift"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{name}+")?;
+            }
+        }
+
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char('+') => write!(f, "plus"),
+            KeyCode::Char(c) if c.is_uppercase() => write!(f, "shift+{}", c.to_lowercase()),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            KeyCode::Left => write!(f, "left"),
+            KeyCode::Right => write!(f, "right"),
+            KeyCode::Up => write!(f, "up"),
+            KeyCode::Down => write!(f, "down"),
+            KeyCode::Enter => write!(f, "enter"),
+            KeyCode::Esc => write!(f, "esc"),
+            KeyCode::Tab => write!(f, "tab"),
+            KeyCode::BackTab => write!(f, "backtab"),
+            KeyCode::Backspace => write!(f, "backspace"),
+            KeyCode::Delete => write!(f, "delete"),
+            KeyCode::Insert => write!(f, "insert"),
+            KeyCode::Home => write!(f, "home"),
+            KeyCode::End => write!(f, "end"),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+impl FromStr for KeyBinding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = s.split('+').collect();
+        let key = parts.pop().unwrap_or_default();
+
+        let mut modifiers = KeyModifiers::NONE;
+        for part in parts {
+            modifiers |= match part.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier `{part}` in `{s}`")),
+            };
+        }
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(c.to_uppercase().next().unwrap_or(c))
+            }
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "plus" => KeyCode::Char('+'),
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                name => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n @ 1..=24) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key `{key}` in `{s}`")),
+                },
+            },
+        };
+
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+impl TryFrom<String> for KeyBinding {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<KeyBinding> for String {
+    fn from(value: KeyBinding) -> Self {
+        value.to_string()
+    }
+}
+
+/// A built in set of key bindings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    /// Arrow keys with Z and X to rotate
+    #[default]
+    Guideline,
+    /// WASD to move with J and K to rotate
+    Wasd,
+    /// hjkl to move and rotate
+    Vim,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 3] = [Preset::Guideline, Preset::Wasd, Preset::Vim];
+
+    /// The keys bound to `action`
+    fn keys(self, action: Action) -> Vec<KeyBinding> {
+        use KeyCode::{Char, Down, Enter, Esc, Left, Right, Up};
+
+        let keys: &[KeyCode] = match (self, action) {
+            (_, Action::Pause) => &[Esc, Char('p')],
+            (_, Action::ToggleGhost) => &[Char('g')],
+            (_, Action::NewTetrimino) => &[Char('n')],
+            (_, Action::Quit) => &[Char('q')],
+
+            (Preset::Guideline, Action::MoveLeft) => &[Left],
+            (Preset::Guideline, Action::MoveRight) => &[Right],
+            (Preset::Guideline, Action::SoftDrop) => &[Down],
+            (Preset::Guideline, Action::HardDrop) => &[Char(' '), Enter],
+            (Preset::Guideline, Action::RotateClockwise) => &[Up, Char('x')],
+            (Preset::Guideline, Action::RotateCounterclockwise) => &[Char('z')],
+            (Preset::Guideline, Action::Rotate180) => &[Char('a')],
+            (Preset::Guideline, Action::Hold) => &[Char('c')],
+
+            (Preset::Wasd, Action::MoveLeft) => &[Char('a')],
+            (Preset::Wasd, Action::MoveRight) => &[Char('d')],
+            (Preset::Wasd, Action::SoftDrop) => &[Char('s')],
+            (Preset::Wasd, Action::HardDrop) => &[Char('w'), Char(' ')],
+            (Preset::Wasd, Action::RotateClockwise) => &[Char('k')],
+            (Preset::Wasd, Action::RotateCounterclockwise) => &[Char('j')],
+            (Preset::Wasd, Action::Rotate180) => &[Char('i')],
+            (Preset::Wasd, Action::Hold) => &[Char('l')],
+
+            (Preset::Vim, Action::MoveLeft) => &[Char('h')],
+            (Preset::Vim, Action::MoveRight) => &[Char('l')],
+            (Preset::Vim, Action::SoftDrop) => &[Char('j')],
+            (Preset::Vim, Action::HardDrop) => &[Char(' ')],
+            (Preset::Vim, Action::RotateClockwise) => &[Char('k')],
+            (Preset::Vim, Action::RotateCounterclockwise) => &[Char('d')],
+            (Preset::Vim, Action::Rotate180) => &[Char('s')],
+            (Preset::Vim, Action::Hold) => &[Char('f')],
+        };
+
+        keys.iter().copied().map(KeyBinding::from).collect()
+    }
+
+    /// The preset after this one, wrapping around
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|preset| *preset == self)
+            .unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Preset::Guideline => "Guideline",
+            Preset::Wasd => "WASD",
+            Preset::Vim => "Vim",
+        })
+    }
+}
+
+/// The keys bound to each [`Action`]
+///
+/// Stored as a preset with per action overrides in `controls.toml`
+/// in the user's config directory:
+///
+/// ```toml
+/// preset = "guideline"
+///
+/// [bindings]
+/// hard_drop = ["space", "shift+up"]
+/// hold = ["c", "ctrl+x"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keymap {
+    #[serde(default)]
+    pub preset: Preset,
+    /// Actions bound differently than in the preset
+    #[serde(default)]
+    bindings: BTreeMap<Action, Vec<KeyBinding>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::from(Preset::default())
+    }
+}
+
+impl From<Preset> for Keymap {
+    fn from(preset: Preset) -> Self {
+        Self {
+            preset,
+            bindings: BTreeMap::new(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Where the keymap is saved
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("console_tetris").join("controls.toml"))
+    }
+
+    /// Load the saved keymap, or the default one if none was saved
+    pub fn load() -> Result<Self, io::Error> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err),
+        };
+
+        toml::from_str(&contents).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {err}", path.display()),
+            )
+        })
+    }
+
+    /// Save the keymap for the next launch
+    pub fn save(&self) -> Result<(), io::Error> {
+        let Some(path) = Self::path() else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no config directory",
+            ));
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let contents =
+            toml::to_string(self).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, contents)
+    }
+
+    /// The keys bound to `action`
+    pub fn keys(&self, action: Action) -> Vec<KeyBinding> {
+        match self.bindings.get(&action) {
+            Some(keys) => keys.to_owned(),
+            None => self.preset.keys(action),
+        }
+    }
+
+    /// The [`Action`] bound to `key`, if any
+    pub fn action(&self, key: KeyEvent) -> Option<Action> {
+        let key = KeyBinding::from(key);
+        Action::ALL
+            .into_iter()
+            .find(|action| self.keys(*action).contains(&key))
+    }
+
+    /// The [`Message`] a key press during play triggers
+    pub fn message(&self, key: KeyEvent) -> Option<Message> {
+        self.action(key).map(Action::message)
+    }
+
+    /// Add `key` to the keys of `action`, taking it away from any other action
+    pub fn bind(&mut self, action: Action, key: KeyBinding) {
+        for other in Action::ALL {
+            let mut keys = self.keys(other);
+            if other != action && keys.contains(&key) {
+                keys.retain(|bound| *bound != key);
+                self.set_keys(other, keys);
+            }
+        }
+
+        let mut keys = self.keys(action);
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+        self.set_keys(action, keys);
+    }
+
+    /// Remove every key bound to `action`
+    pub fn clear(&mut self, action: Action) {
+        self.set_keys(action, vec![]);
+    }
+
+    /// Switch to `preset`, dropping any changed bindings
+    pub fn set_preset(&mut self, preset: Preset) {
+        *self = Self::from(preset);
+    }
+
+    fn set_keys(&mut self, action: Action, keys: Vec<KeyBinding>) {
+        if keys == self.preset.keys(action) {
+            self.bindings.remove(&action);
+        } else {
+            self.bindings.insert(action, keys);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(code: KeyCode, modifiers: KeyModifiers) -> KeyBinding {
+        KeyBinding::new(code, modifiers)
+    }
+
+    /// Check `name` parses to `expected` and `expected` is saved as `name`
+    fn round_trip(name: &str, expected: KeyBinding) {
+        assert_eq!(name.parse(), Ok(expected), "{name}");
+        assert_eq!(expected.to_string(), name);
+        assert!(expected.nameable(), "{name}");
+    }
+
+    #[test]
+    fn modifiers_round_trip() {
+        round_trip("x", binding(KeyCode::Char('x'), KeyModifiers::NONE));
+        round_trip("ctrl+x", binding(KeyCode::Char('x'), KeyModifiers::CONTROL));
+        round_trip(
+            "ctrl+alt+x",
+            binding(
+                KeyCode::Char('x'),
+                KeyModifiers::CONTROL | KeyModifiers::ALT,
+            ),
+        );
+        round_trip("alt+left", binding(KeyCode::Left, KeyModifiers::ALT));
+        round_trip(
+            "ctrl+shift+pageup",
+            binding(KeyCode::PageUp, KeyModifiers::CONTROL | KeyModifiers::SHIFT),
+        );
+
+        // modifiers are read in any order and case, and saved in one order
+        assert_eq!(
+            "Alt+Control+x".parse(),
+            Ok(binding(
+                KeyCode::Char('x'),
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            ))
+        );
+    }
+
+    #[test]
+    fn shift_with_a_letter_is_the_capital_letter() {
+        let capital = binding(KeyCode::Char('A'), KeyModifiers::NONE);
+        round_trip("shift+a", capital);
+        // the terminal reports shift along with the capital letter
+        assert_eq!(
+            KeyBinding::from(KeyEvent::new(KeyCode::Char('A'), KeyModifiers::SHIFT)),
+            capital
+        );
+        round_trip(
+            "ctrl+shift+a",
+            binding(KeyCode::Char('A'), KeyModifiers::CONTROL),
+        );
+    }
+
+    #[test]
+    fn space_and_plus_have_names() {
+        round_trip("space", binding(KeyCode::Char(' '), KeyModifiers::NONE));
+        round_trip("plus", binding(KeyCode::Char('+'), KeyModifiers::NONE));
+        round_trip(
+            "ctrl+plus",
+            binding(KeyCode::Char('+'), KeyModifiers::CONTROL),
+        );
+    }
+
+    #[test]
+    fn function_keys_round_trip() {
+        for n in 1..=24 {
+            round_trip(&format!("f{n}"), binding(KeyCode::F(n), KeyModifiers::NONE));
+        }
+        assert!("f0".parse::<KeyBinding>().is_err());
+        assert!("f25".parse::<KeyBinding>().is_err());
+        assert!(!binding(KeyCode::F(25), KeyModifiers::NONE).nameable());
+    }
+
+    #[test]
+    fn unknown_names_are_rejected() {
+        for name in [
+            "super+x",
+            "ctrl+meta+x",
+            "+",
+            "ctrl+",
+            "",
+            "foo",
+            "ctrl+escapee",
+        ] {
+            assert!(name.parse::<KeyBinding>().is_err(), "{name}");
+        }
+        assert!(!binding(KeyCode::CapsLock, KeyModifiers::NONE).nameable());
+    }
+}
@@ -1,96 +1,331 @@
-use std::{collections::VecDeque, time::Instant};
+use std::{collections::VecDeque, fmt, time::Duration};
 
 use crate::{
-    game_handler::Movement,
-    matrix::{
-        get_matrix_size, Matrix, MinoGrid, MATRIX_HEIGHT, MATRIX_WIDTH, PREVIEW_MATRIX_WIDTH,
-    },
-    tetramino::{Facing, Tetrimino},
-};
-use rand::{rngs::ThreadRng, seq::SliceRandom, thread_rng};
-use ratatui::{
-    prelude::{Constraint, Direction, Layout},
-    widgets::{canvas::Canvas, Block, Borders, Paragraph, StatefulWidget, Widget},
+    clock::{self, frames},
+    matrix::{Matrix, MinoGrid, MATRIX_BUFFER_HEIGHT, MATRIX_HEIGHT, MATRIX_WIDTH},
+    randomizer::{random_seed, Randomizer, RandomizerKind},
+    replay::Input,
+    rotation::{RotationDirection, RotationSystemKind},
+    rules::Rules,
+    scoring::{Callout, Scoring},
+    tetramino::{Facing, TSpin, Tetrimino},
 };
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Movement {
+    Rotate(RotationDirection),
+    Left,
+    Right,
+    Down,
+    Drop,
+}
+
+/// The level gravity stops speeding up at
+///
+/// Past level 115 the gravity curve would go below zero
+const MAX_GRAVITY_LEVEL: i32 = 20;
 
-/// A [`Bag`] is a self-filling [`Vec<Tetrimino>`]s
+/// The upcoming Tetriminos, refilled from a [`Randomizer`]
 ///
-/// Calls to `next()` will yield shuffled sequences
+/// The same seed and randomizer always give the same sequence,
+/// so it is saved as the seed and how far along the sequence it is
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "SavedNextQueue", into = "SavedNextQueue")
+)]
 pub struct NextQueue {
     queue: VecDeque<Tetrimino>,
-    bag: Vec<Tetrimino>,
-    rng: ThreadRng,
+    randomizer: Box<dyn Randomizer>,
+    rng: ChaCha8Rng,
+    rotation_system: RotationSystemKind,
+    kind: RandomizerKind,
+    seed: u64,
+    /// How many Tetriminos have been generated
+    generated: u64,
+}
+
+/// How a [`NextQueue`] is saved
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SavedNextQueue {
+    randomizer: RandomizerKind,
+    rotation_system: RotationSystemKind,
+    seed: u64,
+    generated: u64,
+}
+
+#[cfg(feature = "serde")]
+impl From<NextQueue> for SavedNextQueue {
+    fn from(queue: NextQueue) -> Self {
+        Self {
+            randomizer: queue.kind,
+            rotation_system: queue.rotation_system,
+            seed: queue.seed,
+            generated: queue.generated,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<SavedNextQueue> for NextQueue {
+    fn from(saved: SavedNextQueue) -> Self {
+        let mut queue = NextQueue::new(saved.randomizer, saved.rotation_system, saved.seed);
+        while queue.generated < saved.generated {
+            queue.next();
+        }
+        queue
+    }
 }
 
+/// How many upcoming Tetriminos are known, the most the next queue can show
+pub const NEXT_QUEUE_LENGTH: usize = 7;
+
 impl PartialEq for NextQueue {
     fn eq(&self, other: &Self) -> bool {
-        self.bag == other.bag
+        self.queue == other.queue
     }
 }
 
 impl Eq for NextQueue {}
 
-impl Default for NextQueue {
-    fn default() -> Self {
+impl NextQueue {
+    pub fn new(randomizer: RandomizerKind, rotation_system: RotationSystemKind, seed: u64) -> Self {
         let mut queue = Self {
             queue: VecDeque::new(),
-            bag: vec![],
-            rng: thread_rng(),
+            randomizer: randomizer.build(),
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            rotation_system,
+            kind: randomizer,
+            seed,
+            generated: 0,
         };
 
-        let mut next = (0..6).map(|_| queue.next_bag()).collect();
-        queue.queue.append(&mut next);
+        for _ in 0..NEXT_QUEUE_LENGTH {
+            let next = queue.generate();
+            queue.queue.push_back(next);
+        }
 
         queue
     }
-}
 
-impl NextQueue {
+    /// The kind of randomizer the sequence comes from
+    pub fn randomizer(&self) -> RandomizerKind {
+        self.kind
+    }
+
+    /// The seed the sequence comes from
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
     pub fn get_queue(&self) -> Vec<Tetrimino> {
         Vec::from(self.queue.to_owned())
     }
 
-    fn next_bag(&mut self) -> Tetrimino {
-        // fill and shuffle if empty
-        if self.bag.is_empty() {
-            self.bag = Tetrimino::all();
-            self.bag.shuffle(&mut self.rng);
-        }
-
-        // bag is fed back to front but that order doesn't matter
-        self.bag.pop().expect("Bag was empty!")
+    fn generate(&mut self) -> Tetrimino {
+        self.generated += 1;
+        Tetrimino::new(self.randomizer.next(&mut self.rng), self.rotation_system)
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Tetrimino {
-        // move a bag tetrimino into the queue
-        let next = self.next_bag();
+        // move a generated tetrimino into the queue
+        let next = self.generate();
         self.queue.push_back(next);
         // provide an element of the queue
         self.queue.pop_front().expect("Queue was empty!")
     }
 }
 
+/// The way a game ended
+///
+/// Named after the guideline top out conditions, or finished in modes with a line goal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum GameOverReason {
+    /// A Tetrimino spawned overlapping the stack
+    BlockOut,
+    /// A Tetrimino locked entirely above the visible matrix
+    LockOut,
+    /// A Tetrimino locked partly above the visible matrix
+    PartialLockOut,
+    /// The line goal of the mode was reached
+    Finished,
+}
+
+impl fmt::Display for GameOverReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::BlockOut => "Block out",
+            Self::LockOut => "Lock out",
+            Self::PartialLockOut => "Partial lock out",
+            Self::Finished => "Finished",
+        })
+    }
+}
+
+/// What happened when a Tetrimino locked
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LockResult {
+    pub lines_cleared: usize,
+    pub t_spin: Option<TSpin>,
+    /// The clear left the matrix empty
+    pub perfect_clear: bool,
+}
+
+impl LockResult {
+    /// The name of the clear, if it is worth calling out
+    pub fn name(&self) -> Option<String> {
+        let lines = match self.lines_cleared {
+            0 => None,
+            1 => Some("Single"),
+            2 => Some("Double"),
+            3 => Some("Triple"),
+            _ => Some("Tetris"),
+        };
+
+        let Some(t_spin) = self.t_spin else {
+            return lines.map(str::to_owned);
+        };
+        let t_spin = match t_spin {
+            TSpin::Mini => "T-Spin Mini",
+            TSpin::Full => "T-Spin",
+        };
+
+        Some(match lines {
+            Some(lines) => format!("{t_spin} {lines}"),
+            None => t_spin.to_owned(),
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Game {
     pub tetrimino: Tetrimino,
     pub matrix: Matrix,
 }
 
 impl Game {
-    pub fn new_tetrimino(&mut self, tetrimino: Tetrimino) -> bool {
+    /// Create an empty matrix, including the buffer zone, and spawn `tetrimino`
+    pub fn new(tetrimino: Tetrimino) -> Self {
+        let mut game = Self {
+            tetrimino,
+            matrix: Matrix::new(
+                (MATRIX_HEIGHT + MATRIX_BUFFER_HEIGHT).into(),
+                MATRIX_WIDTH.into(),
+                Facing::North,
+            ),
+        };
+        // an empty matrix can't block out
+        let _ = game.spawn(game.tetrimino.to_owned());
+        game
+    }
+
+    /// Replace the current Tetrimino with `tetrimino` at the spawn point
+    ///
+    /// Like the guideline, the Tetrimino immediately drops one row if nothing is in the way
+    ///
+    /// Returns [`GameOverReason::BlockOut`] if `tetrimino` overlaps the stack
+    pub fn spawn(&mut self, tetrimino: Tetrimino) -> Result<(), GameOverReason> {
+        if tetrimino.position_invalid(0, 0, &self.matrix).is_some() {
+            return Err(GameOverReason::BlockOut);
+        }
+
+        self.tetrimino = tetrimino;
+        self.tetrimino.move_position(0, -1, &self.matrix);
+        Ok(())
+    }
+
+    /// Check if locking the current Tetrimino where it is would be a lock out
+    pub fn lock_out(&self) -> Option<GameOverReason> {
+        let minos = self.tetrimino.get_minos();
+        let hidden = minos
+            .iter()
+            .filter(|mino| mino.row >= MATRIX_HEIGHT.into())
+            .count();
+
+        if hidden == minos.len() {
+            Some(GameOverReason::LockOut)
+        } else if hidden > 0 {
+            Some(GameOverReason::PartialLockOut)
+        } else {
+            None
+        }
+    }
+
+    /// Lock the current Tetrimino into the matrix and replace it with `tetrimino`
+    ///
+    /// Returns the lines cleared and T-spin made by the lock,
+    /// or [`GameOverReason::BlockOut`] if `tetrimino` could not be placed
+    pub fn new_tetrimino(&mut self, tetrimino: Tetrimino) -> Result<LockResult, GameOverReason> {
+        // T-spins depend on the stack before it is changed
+        let t_spin = self.tetrimino.t_spin(&self.matrix);
+
         // lock the current Tetrimino
         for mino in self.tetrimino.get_minos() {
             self.matrix.set_mino(mino.to_owned());
         }
 
+        // clear any completed lines
+        let lines_cleared = self.matrix.clear_lines();
+
+        let perfect_clear = lines_cleared > 0 && self.matrix.get_minos().is_empty();
+
         // new Tetrimino
-        return if tetrimino.position_invalid(0, 0, &self.matrix).is_some() {
-            false
-        } else {
-            self.tetrimino = tetrimino;
-            true
-        };
+        self.spawn(tetrimino)?;
+        Ok(LockResult {
+            lines_cleared,
+            t_spin,
+            perfect_clear,
+        })
+    }
+
+    /// The number of rows the current Tetrimino can fall before it lands
+    pub fn drop_distance(&self) -> i32 {
+        let mut distance = 0;
+        while self
+            .tetrimino
+            .position_invalid(0, -(distance + 1), &self.matrix)
+            .is_none()
+        {
+            distance += 1;
+        }
+        distance
+    }
+
+    /// Move the current Tetrimino straight down to where it lands
+    ///
+    /// Returns the number of rows it fell
+    pub fn hard_drop(&mut self) -> u32 {
+        let distance = self.drop_distance();
+        // a Tetrimino already on the stack keeps its last rotation for T-spins
+        if distance > 0 {
+            self.tetrimino.move_position(0, -distance, &self.matrix);
+        }
+        distance as u32
+    }
+
+    /// Check if the current Tetrimino is resting on the stack or the floor
+    pub fn grounded(&self) -> bool {
+        self.tetrimino
+            .position_invalid(0, -1, &self.matrix)
+            .is_some()
+    }
+
+    /// A copy of the current Tetrimino at the position it would land if hard dropped
+    pub fn ghost(&self) -> Tetrimino {
+        let mut ghost = self.tetrimino.to_owned();
+        ghost.move_position(0, -self.drop_distance(), &self.matrix);
+        ghost
     }
 
     pub fn apply_movement(&mut self, movement: Movement) -> bool {
@@ -99,106 +334,796 @@ impl Game {
             Movement::Left => self.tetrimino.move_position(-1, 0, &self.matrix),
             Movement::Right => self.tetrimino.move_position(1, 0, &self.matrix),
             Movement::Down => self.tetrimino.move_position(0, -1, &self.matrix),
-            Movement::Drop => true,
+            Movement::Drop => {
+                self.hard_drop();
+                true
+            }
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct GameState {
-    pub game_over: bool,
+    /// Why the game ended, if it is over
+    pub game_over: Option<GameOverReason>,
     pub next_queue: NextQueue,
     pub game: Game,
-    pub level: i32,
-    pub last_tick: Instant,
+    pub rules: Rules,
+    /// The seed the Tetrimino sequence is generated from
+    pub seed: u64,
+    pub scoring: Scoring,
+    /// The last lock worth calling out on the HUD
+    pub callout: Option<Callout>,
+    /// The Tetrimino in the hold slot
+    pub hold: Option<Tetrimino>,
+    /// Whether hold has been used since the last Tetrimino locked
+    pub hold_used: bool,
+    /// The number of frames played, not counting pauses
+    pub frame: u64,
+    /// The frame gravity last moved the current Tetrimino down
+    pub last_drop: u64,
+    /// The frame the current Tetrimino became grounded, or last reset the lock delay
+    ///
+    /// Kept while the Tetrimino is kicked up off the stack, so landing again doesn't
+    /// restart the lock delay. Cleared when it reaches a new lowest row
+    pub lock_timer: Option<u64>,
+    /// Lock delay resets used since the current Tetrimino reached `lowest_row`
+    pub lock_resets: u32,
+    /// The lowest row a mino of the current Tetrimino has reached
+    ///
+    /// Minos rather than the bound-box, so turning in place doesn't count as falling
+    pub lowest_row: i32,
+    /// Frames don't advance while paused
+    paused: bool,
+    /// How many times faster than gravity the Tetrimino falls while soft drop is held
+    pub soft_drop: Option<u32>,
+    /// Every [`Input`] that changed the game, with the frame it was applied on
+    pub inputs: Vec<(u64, Input)>,
 }
 
 impl Default for GameState {
     fn default() -> Self {
-        let mut next_queue = NextQueue::default();
-        let tetrimino = next_queue.next();
+        Self::new(Rules::default(), random_seed())
+    }
+}
+
+impl GameState {
+    pub fn new(rules: Rules, seed: u64) -> Self {
+        let mut next_queue = NextQueue::new(rules.randomizer, rules.rotation_system, seed);
+        let game = Game::new(next_queue.next());
+        let lowest_row = game.tetrimino.bottom_row();
 
         Self {
-            game_over: false,
+            game_over: None,
             next_queue,
-            game: Game {
-                tetrimino,
-                matrix: Matrix::new(MATRIX_HEIGHT.into(), MATRIX_WIDTH.into(), Facing::North),
-            },
-            level: 1,
-            last_tick: Instant::now(),
+            game,
+            rules,
+            seed,
+            scoring: Scoring::default(),
+            callout: None,
+            hold: None,
+            hold_used: false,
+            frame: 0,
+            last_drop: 0,
+            lock_timer: None,
+            lock_resets: 0,
+            lowest_row,
+            paused: false,
+            soft_drop: None,
+            inputs: vec![],
         }
     }
-}
 
-impl GameState {
-    /// The current time to move down one line in seconds
-    fn current_drop_time(&self) -> f64 {
-        (0.8 - ((self.level - 1) as f64 * 0.007)).powi(self.level - 1)
-    }
-
-    /// Runs the gravity check
-    pub fn tick(&mut self) -> bool {
-        let now = Instant::now();
-        if now.duration_since(self.last_tick).as_secs_f64() > self.current_drop_time() {
-            self.last_tick = now;
-            if !self.game.apply_movement(Movement::Down) {
-                return self.game.new_tetrimino(self.next_queue.next());
+    /// Time spent playing, not counting pauses
+    pub fn play_time(&self) -> Duration {
+        clock::duration(self.frame)
+    }
+
+    /// Stop the game, and with it the frames
+    fn end_game(&mut self, reason: GameOverReason) {
+        self.game_over = Some(reason);
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Freeze gravity and the lock delay
+    ///
+    /// Soft drop is released, since the key may be let go while paused
+    pub fn pause(&mut self) {
+        if self.soft_drop.is_some() {
+            self.input(Input::SoftDrop(None));
+        }
+        self.paused = true;
+    }
+
+    /// Continue the game from the frame it was paused on
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// The current number of frames to move down one line
+    ///
+    /// Gravity moves at most one line per frame
+    fn current_drop_frames(&self) -> u64 {
+        let level = self.scoring.level.min(MAX_GRAVITY_LEVEL);
+        let drop_time = (0.8 - ((level - 1) as f64 * 0.007)).powi(level - 1);
+        let drop_time = match self.soft_drop {
+            Some(factor) => drop_time / factor.max(1) as f64,
+            None => drop_time,
+        };
+        frames(Duration::from_secs_f64(drop_time)).max(1)
+    }
+
+    /// Start soft dropping at `factor` times the speed of gravity, or stop with [`None`]
+    ///
+    /// Starting a soft drop moves the Tetrimino down one row right away
+    pub fn set_soft_drop(&mut self, factor: Option<u32>) {
+        if self.paused() {
+            return;
+        }
+
+        if self.soft_drop.is_none() && factor.is_some() {
+            self.apply_movement(Movement::Down);
+            self.last_drop = self.frame;
+        }
+        self.soft_drop = factor;
+    }
+
+    /// The next frame [`GameState::step`] has work to do on
+    ///
+    /// The earlier of the next gravity step and the end of the lock delay,
+    /// or [`None`] while the game is paused or over
+    pub fn next_deadline(&self) -> Option<u64> {
+        if self.paused() || self.game_over.is_some() {
+            return None;
+        }
+
+        let gravity = self.last_drop + self.current_drop_frames();
+        Some(match self.lock_timer {
+            Some(grounded_at) if self.game.grounded() => {
+                gravity.min(grounded_at + frames(self.rules.lock_delay))
+            }
+            _ => gravity,
+        })
+    }
+
+    /// Lock the current Tetrimino and spawn the next one from the [`NextQueue`]
+    ///
+    /// Returns `false` and ends the game if the next Tetrimino could not be placed.
+    /// Reaching the line goal of the mode ends the game too
+    pub fn next_tetrimino(&mut self) -> bool {
+        // a lock out ends the game before the Tetrimino is locked
+        match self.game.lock_out() {
+            Some(GameOverReason::PartialLockOut) if !self.rules.partial_lock_out => (),
+            Some(reason) => {
+                self.end_game(reason);
+                return false;
+            }
+            None => (),
+        }
+
+        self.scoring.pieces += 1;
+
+        match self.game.new_tetrimino(self.next_queue.next()) {
+            Ok(result) => {
+                let callout = self.scoring.add_lock(result);
+                if !callout.lines().is_empty() {
+                    self.callout = Some(callout);
+                }
+                self.hold_used = false;
+                self.reset_lock();
+
+                let goal = self.rules.mode.line_goal();
+                if goal.is_some_and(|goal| self.scoring.lines >= goal) {
+                    self.end_game(GameOverReason::Finished);
+                }
+                true
+            }
+            Err(reason) => {
+                self.end_game(reason);
+                false
+            }
+        }
+    }
+
+    /// Swap the current Tetrimino with the held one
+    ///
+    /// If nothing is held, the next Tetrimino is taken from the [`NextQueue`].
+    /// The held Tetrimino returns to its spawn orientation.
+    ///
+    /// Returns `false` if hold has already been used since the last lock,
+    /// and does nothing while paused or after the game is over
+    pub fn hold(&mut self) -> bool {
+        if self.hold_used || self.paused() || self.game_over.is_some() {
+            return false;
+        }
+
+        let tetrimino = match self.hold.take() {
+            Some(held) => held,
+            None => self.next_queue.next(),
+        };
+
+        let current = self.game.tetrimino.tetrimino_type();
+
+        // the swapped in Tetrimino must fit at the spawn point
+        if let Err(reason) = self.game.spawn(tetrimino) {
+            self.end_game(reason);
+            return false;
+        }
+
+        self.hold = Some(Tetrimino::new(current, self.rules.rotation_system));
+        self.hold_used = true;
+        self.last_drop = self.frame;
+        self.reset_lock();
+
+        true
+    }
+
+    /// Clear the lock delay state for a newly spawned Tetrimino
+    fn reset_lock(&mut self) {
+        let row = self.game.tetrimino.bottom_row();
+        self.lock_timer = None;
+        self.lock_resets = 0;
+        self.lowest_row = row;
+    }
+
+    /// Refresh the lock resets and the lock delay if the current Tetrimino reached
+    /// a new lowest row
+    fn update_lowest_row(&mut self) {
+        let row = self.game.tetrimino.bottom_row();
+        if row < self.lowest_row {
+            self.lowest_row = row;
+            self.lock_resets = 0;
+            self.lock_timer = None;
+        }
+    }
+
+    /// Hard drop the current Tetrimino, lock it immediately and spawn the next one
+    ///
+    /// Returns the number of rows the Tetrimino fell
+    pub fn hard_drop(&mut self) -> u32 {
+        let distance = self.game.hard_drop();
+        self.scoring.add_drop(Movement::Drop, distance);
+        self.next_tetrimino();
+        distance
+    }
+
+    /// Apply an [`Input`] from the player and remember it for replays
+    ///
+    /// Only inputs that changed the game are remembered, so a key held against a wall
+    /// doesn't fill the replay. Inputs do nothing while paused or after the game is over.
+    ///
+    /// Returns whether the input did anything
+    pub fn input(&mut self, input: Input) -> bool {
+        if self.paused() || self.game_over.is_some() {
+            return false;
+        }
+
+        let changed = match input {
+            Input::Move(movement) => self.apply_movement(movement),
+            Input::Hold => self.hold(),
+            Input::NewTetrimino => self.next_tetrimino(),
+            Input::SoftDrop(factor) => {
+                let changed = self.soft_drop != factor;
+                self.set_soft_drop(factor);
+                changed
+            }
+        };
+
+        // an input that tops out fails, but still ends the game
+        if changed || self.game_over.is_some() {
+            self.inputs.push((self.frame, input));
+        }
+        changed
+    }
+
+    /// Apply a player movement to the current Tetrimino, awarding any drop points
+    pub fn apply_movement(&mut self, movement: Movement) -> bool {
+        if self.paused() {
+            return false;
+        }
+
+        if movement == Movement::Drop {
+            self.hard_drop();
+            return true;
+        }
+
+        let moved = self.game.apply_movement(movement);
+        if !moved {
+            return false;
+        }
+
+        // kicks can move a Tetrimino down as well as soft drop
+        self.update_lowest_row();
+        if movement == Movement::Down {
+            self.scoring.add_drop(movement, 1);
+        } else if self.lock_timer.is_some() && self.lock_resets < self.rules.max_lock_resets {
+            // a successful move or rotation restarts the lock delay
+            self.lock_timer = Some(self.frame);
+            self.lock_resets += 1;
+        }
+
+        // the lock delay starts on the frame the Tetrimino lands, not the next gravity step
+        if self.lock_timer.is_none() && self.game.grounded() {
+            self.lock_timer = Some(self.frame);
+        }
+
+        true
+    }
+
+    /// Advance the game by one frame, running gravity and the lock delay
+    ///
+    /// Does nothing while paused. Returns `false` once the game is over
+    pub fn step(&mut self) -> bool {
+        if self.game_over.is_some() {
+            return false;
+        }
+
+        if self.paused() {
+            return true;
+        }
+
+        self.frame += 1;
+        let now = self.frame;
+        if now - self.last_drop >= self.current_drop_frames() {
+            self.last_drop = now;
+            if self.game.apply_movement(Movement::Down) {
+                if self.soft_drop.is_some() {
+                    self.scoring.add_drop(Movement::Down, 1);
+                }
+                self.update_lowest_row();
             }
         }
+
+        if self.game.grounded() {
+            // lock once the Tetrimino has been grounded for the lock delay
+            let grounded_at = *self.lock_timer.get_or_insert(now);
+            if now - grounded_at >= frames(self.rules.lock_delay) {
+                return self.next_tetrimino();
+            }
+        }
+
         true
     }
 }
 
-pub struct Tetris;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rotation::RotationDirection,
+        rules::GameMode,
+        tetramino::{Mino, TetriminoType},
+    };
 
-impl StatefulWidget for Tetris {
-    type State = GameState;
-    fn render(
-        self,
-        area: ratatui::prelude::Rect,
-        buf: &mut ratatui::prelude::Buffer,
-        state: &mut Self::State,
-    ) {
-        let Some((board_width, board_height, preview_width, margin)) =
-            get_matrix_size(area.width, area.height)
-        else {
-            Paragraph::new("This terminal is too small to play Tetris!").render(area, buf);
-            return;
+    /// The types of the first `count` Tetriminos of a `randomizer` queue seeded with `seed`
+    fn deal(randomizer: RandomizerKind, seed: u64, count: usize) -> Vec<TetriminoType> {
+        let mut next_queue = NextQueue::new(randomizer, RotationSystemKind::Srs, seed);
+        (0..count)
+            .map(|_| next_queue.next().tetrimino_type())
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_deals_the_same_sequence() {
+        for randomizer in RandomizerKind::ALL {
+            let dealt = deal(randomizer, 1234, 200);
+            assert_eq!(dealt, deal(randomizer, 1234, 200), "{randomizer}");
+            assert_ne!(dealt, deal(randomizer, 1235, 200), "{randomizer}");
+        }
+    }
+
+    #[test]
+    fn gravity_stops_speeding_up_at_high_levels() {
+        let mut gamestate = GameState::new(Rules::default(), 1);
+        gamestate.scoring.level = MAX_GRAVITY_LEVEL;
+        let fastest = gamestate.current_drop_frames();
+
+        gamestate.scoring.level = 200;
+        assert_eq!(gamestate.current_drop_frames(), fastest);
+        assert!(gamestate.next_deadline().is_some());
+
+        // pieces fall and lock every few frames until the stack tops out
+        while gamestate.step() {}
+        assert!(gamestate.game_over.is_some());
+    }
+
+    /// A game with a T in place of the first Tetrimino
+    fn gamestate_with_t(rules: Rules) -> GameState {
+        let mut gamestate = GameState::new(rules, 1);
+        gamestate
+            .game
+            .spawn(Tetrimino::new(TetriminoType::T, RotationSystemKind::Srs))
+            .unwrap();
+        gamestate
+    }
+
+    /// Move the current Tetrimino clear of the spawn point with its lowest mino on `row`
+    fn move_to_row(gamestate: &mut GameState, row: i32) {
+        let game = &mut gamestate.game;
+        let lowest = game.tetrimino.get_minos().iter().map(|mino| mino.row).min();
+        assert!(game
+            .tetrimino
+            .move_position(4, row - lowest.unwrap(), &game.matrix));
+    }
+
+    #[test]
+    fn block_out_when_the_spawn_point_is_taken() {
+        let mut gamestate = GameState::new(Rules::default(), 1);
+        gamestate.game.hard_drop();
+
+        // fill around the spawn point, leaving a column open so nothing is cleared
+        let mino = gamestate.game.tetrimino.get_minos()[0].to_owned();
+        for row in MATRIX_HEIGHT - 2..MATRIX_HEIGHT + 4 {
+            for col in 0..MATRIX_WIDTH - 1 {
+                gamestate.game.matrix.set_mino(Mino {
+                    col: col.into(),
+                    row: row.into(),
+                    ..mino.to_owned()
+                });
+            }
+        }
+
+        assert!(!gamestate.next_tetrimino());
+        assert_eq!(gamestate.game_over, Some(GameOverReason::BlockOut));
+    }
+
+    #[test]
+    fn lock_out_above_the_visible_matrix() {
+        let mut gamestate = gamestate_with_t(Rules::default());
+        move_to_row(&mut gamestate, MATRIX_HEIGHT.into());
+
+        assert!(!gamestate.next_tetrimino());
+        assert_eq!(gamestate.game_over, Some(GameOverReason::LockOut));
+    }
+
+    #[test]
+    fn partial_lock_out_only_when_the_rules_ask_for_it() {
+        let mut gamestate = gamestate_with_t(Rules::default());
+        move_to_row(&mut gamestate, (MATRIX_HEIGHT - 1).into());
+
+        assert!(gamestate.next_tetrimino());
+        assert_eq!(gamestate.game_over, None);
+
+        // classic plays without the buffer zone to lock in
+        let mut gamestate = gamestate_with_t(Rules::from(GameMode::Classic));
+        move_to_row(&mut gamestate, (MATRIX_HEIGHT - 1).into());
+
+        assert!(!gamestate.next_tetrimino());
+        assert_eq!(gamestate.game_over, Some(GameOverReason::PartialLockOut));
+    }
+
+    #[test]
+    fn hard_drop_keeps_a_t_spin() {
+        let mut gamestate = gamestate_with_t(Rules::default());
+
+        // a slot two rows deep with an overhang on the left
+        let mino = gamestate.game.tetrimino.get_minos()[0].to_owned();
+        let filled = (0..10)
+            .filter(|col| *col != 4)
+            .map(|col| (col, 0))
+            .chain(
+                (0..10)
+                    .filter(|col| !(3..=5).contains(col))
+                    .map(|col| (col, 1)),
+            )
+            .chain([(3, 2)]);
+        for (col, row) in filled {
+            gamestate.game.matrix.set_mino(Mino {
+                col,
+                row,
+                ..mino.to_owned()
+            });
+        }
+
+        // drop the T down the slot on its side and turn it in
+        let clockwise = Input::Move(Movement::Rotate(RotationDirection::Clockwise));
+        assert!(gamestate.input(clockwise));
+        while gamestate.game.apply_movement(Movement::Down) {}
+        assert!(gamestate.input(clockwise));
+
+        assert!(gamestate.input(Input::Move(Movement::Drop)));
+        assert_eq!(
+            gamestate.callout.map(|callout| callout.result),
+            Some(LockResult {
+                lines_cleared: 2,
+                t_spin: Some(TSpin::Full),
+                perfect_clear: false,
+            })
+        );
+    }
+
+    #[test]
+    fn input_against_a_wall_is_not_recorded() {
+        let mut gamestate = GameState::new(Rules::default(), 1);
+
+        let mut moves = 0;
+        while gamestate.input(Input::Move(Movement::Left)) {
+            moves += 1;
+        }
+        for _ in 0..10 {
+            assert!(!gamestate.input(Input::Move(Movement::Left)));
+        }
+
+        assert!(moves > 0);
+        assert_eq!(
+            gamestate.inputs,
+            vec![(0, Input::Move(Movement::Left)); moves]
+        );
+    }
+
+    #[test]
+    fn input_is_ignored_while_paused() {
+        let mut gamestate = GameState::new(Rules::default(), 1);
+        gamestate.pause();
+
+        assert!(!gamestate.input(Input::Move(Movement::Right)));
+        assert!(!gamestate.input(Input::Hold));
+        assert!(!gamestate.input(Input::Move(Movement::Drop)));
+        assert_eq!(gamestate.scoring.pieces, 0);
+        assert!(gamestate.hold.is_none());
+        assert!(gamestate.inputs.is_empty());
+
+        gamestate.resume();
+        assert!(gamestate.input(Input::Hold));
+        assert_eq!(gamestate.inputs, vec![(0, Input::Hold)]);
+    }
+
+    #[test]
+    fn repeated_soft_drop_is_recorded_once() {
+        let mut gamestate = GameState::new(Rules::default(), 1);
+
+        assert!(gamestate.input(Input::SoftDrop(Some(20))));
+        assert!(!gamestate.input(Input::SoftDrop(Some(20))));
+        gamestate.step();
+        assert!(gamestate.input(Input::SoftDrop(None)));
+        assert!(!gamestate.input(Input::SoftDrop(None)));
+
+        assert_eq!(
+            gamestate.inputs,
+            vec![(0, Input::SoftDrop(Some(20))), (1, Input::SoftDrop(None))]
+        );
+    }
+
+    #[test]
+    fn sprint_finishes_at_the_line_goal() {
+        let mut gamestate = GameState::new(Rules::from(GameMode::Sprint), 1);
+        let goal = GameMode::Sprint
+            .line_goal()
+            .expect("sprint has a line goal");
+
+        gamestate.scoring.lines = goal - 1;
+        assert!(gamestate.input(Input::Move(Movement::Drop)));
+        assert_eq!(gamestate.game_over, None);
+
+        gamestate.scoring.lines = goal;
+        assert!(gamestate.input(Input::Move(Movement::Drop)));
+        assert_eq!(gamestate.game_over, Some(GameOverReason::Finished));
+        assert!(!gamestate.step());
+    }
+
+    #[test]
+    fn marathon_has_no_line_goal() {
+        let mut gamestate = GameState::new(Rules::from(GameMode::Marathon), 1);
+
+        gamestate.scoring.lines = 1000;
+        assert!(gamestate.input(Input::Move(Movement::Drop)));
+        assert_eq!(gamestate.game_over, None);
+    }
+
+    /// A game with a `tetrimino_type` soft dropped onto the empty floor on frame 0
+    fn landed(rules: Rules, tetrimino_type: TetriminoType) -> GameState {
+        let mut gamestate = GameState::new(rules, 1);
+        gamestate
+            .game
+            .spawn(Tetrimino::new(tetrimino_type, RotationSystemKind::Srs))
+            .unwrap();
+        while gamestate.apply_movement(Movement::Down) {}
+        assert_eq!(gamestate.lock_timer, Some(0));
+        gamestate
+    }
+
+    /// Step until the current Tetrimino locks, returning the frame it locked on
+    fn step_until_lock(gamestate: &mut GameState, mut input: impl FnMut(&mut GameState)) -> u64 {
+        let pieces = gamestate.scoring.pieces;
+        while gamestate.scoring.pieces == pieces {
+            assert!(gamestate.frame < 10_000, "never locked");
+            input(gamestate);
+            assert!(gamestate.step());
+        }
+        gamestate.frame
+    }
+
+    #[test]
+    fn lock_delay_locks_after_landing() {
+        let rules = Rules::default();
+        let lock_delay = frames(rules.lock_delay);
+        let mut gamestate = landed(rules, TetriminoType::T);
+
+        assert_eq!(gamestate.next_deadline(), Some(lock_delay));
+        assert_eq!(step_until_lock(&mut gamestate, |_| ()), lock_delay);
+    }
+
+    #[test]
+    fn moving_or_rotating_restarts_the_lock_delay() {
+        let rules = Rules::default();
+        let lock_delay = frames(rules.lock_delay);
+
+        for movement in [
+            Movement::Left,
+            Movement::Rotate(RotationDirection::Counterclockwise),
+        ] {
+            let mut gamestate = landed(rules.to_owned(), TetriminoType::T);
+            let locked = step_until_lock(&mut gamestate, |gamestate| {
+                if gamestate.frame == 20 {
+                    assert!(gamestate.apply_movement(movement));
+                    assert_eq!(gamestate.lock_timer, Some(20));
+                    assert_eq!(gamestate.lock_resets, 1);
+                }
+            });
+            assert_eq!(locked, 20 + lock_delay, "{movement:?}");
+        }
+    }
+
+    #[test]
+    fn lock_resets_run_out() {
+        let rules = Rules::default();
+        let lock_delay = frames(rules.lock_delay);
+        let mut gamestate = landed(rules.to_owned(), TetriminoType::T);
+
+        let locked = step_until_lock(&mut gamestate, |gamestate| {
+            if gamestate.frame % 10 == 0 {
+                let movement = match gamestate.frame % 20 {
+                    0 => Movement::Left,
+                    _ => Movement::Right,
+                };
+                gamestate.apply_movement(movement);
+            }
+        });
+        // the first reset is on frame 0 and the last on frame 140,
+        // the moves after it don't restart the delay
+        assert_eq!(locked, 10 * (rules.max_lock_resets as u64 - 1) + lock_delay);
+    }
+
+    #[test]
+    fn lock_resets_run_out_when_kicked_up() {
+        let rules = Rules::default();
+        // the last frame the lock delay can run to when turning every `interval` frames
+        let cap = |interval: u64| {
+            interval * (rules.max_lock_resets as u64 - 1) + frames(rules.lock_delay)
         };
 
-        let vertical_layout = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(board_height),
-                Constraint::Length(area.height - board_height),
-            ])
-            .split(area);
-
-        let layout = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([
-                Constraint::Length(margin),
-                Constraint::Length(board_width),
-                Constraint::Length(preview_width),
-                Constraint::Length(margin),
-            ])
-            .split(vertical_layout[0]);
-
-        Canvas::default()
-            .block(Block::default().title("TETRIS").borders(Borders::ALL))
-            .x_bounds([0.0, MATRIX_WIDTH.into()])
-            .y_bounds([0.0, MATRIX_HEIGHT.into()])
-            .marker(ratatui::symbols::Marker::Block)
-            .paint(|ctx| ctx.draw(&state.game))
-            .render(layout[1], buf);
-
-        Canvas::default()
-            .block(Block::default().title("TETRIS").borders(Borders::ALL))
-            .x_bounds([0.0, PREVIEW_MATRIX_WIDTH.into()])
-            .y_bounds([0.0, MATRIX_HEIGHT.into()])
-            .marker(ratatui::symbols::Marker::Block)
-            .paint(|ctx| ctx.draw(&state.next_queue))
-            .render(layout[2], buf);
+        for tetrimino_type in [
+            TetriminoType::I,
+            TetriminoType::T,
+            TetriminoType::S,
+            TetriminoType::Z,
+            TetriminoType::L,
+            TetriminoType::J,
+        ] {
+            // turning back and forth, each turn kicking up off the floor or landing again
+            let mut gamestate = landed(rules.to_owned(), tetrimino_type);
+            let locked = step_until_lock(&mut gamestate, |gamestate| {
+                if gamestate.frame % 10 == 0 {
+                    let direction = match gamestate.frame % 20 {
+                        0 => RotationDirection::Clockwise,
+                        _ => RotationDirection::Counterclockwise,
+                    };
+                    gamestate.apply_movement(Movement::Rotate(direction));
+                }
+            });
+            // landing again may take a gravity step
+            assert!(
+                locked <= cap(10) + gamestate.current_drop_frames(),
+                "{tetrimino_type:?} locked on frame {locked}"
+            );
+
+            let mut gamestate = landed(rules.to_owned(), tetrimino_type);
+            let locked = step_until_lock(&mut gamestate, |gamestate| {
+                if gamestate.frame % 20 == 0 {
+                    gamestate.apply_movement(Movement::Rotate(RotationDirection::Half));
+                }
+            });
+            assert!(
+                locked <= cap(20) + gamestate.current_drop_frames(),
+                "{tetrimino_type:?} locked on frame {locked} turning by half"
+            );
+        }
+    }
+
+    #[test]
+    fn no_lock_resets_lock_on_time() {
+        for mode in [GameMode::Classic, GameMode::Arcade] {
+            let rules = Rules::from(mode);
+            assert_eq!(rules.max_lock_resets, 0);
+            let lock_delay = frames(rules.lock_delay);
+            let mut gamestate = landed(rules, TetriminoType::T);
+
+            let locked = step_until_lock(&mut gamestate, |gamestate| {
+                if gamestate.frame == 10 {
+                    assert!(gamestate.apply_movement(Movement::Left));
+                    assert_eq!(gamestate.lock_resets, 0);
+                }
+            });
+            assert_eq!(locked, lock_delay, "{mode}");
+        }
+    }
+
+    #[test]
+    fn hold_takes_the_next_tetrimino_when_empty() {
+        let mut gamestate = GameState::new(Rules::default(), 1);
+        let first = gamestate.game.tetrimino.tetrimino_type();
+        let queue = gamestate.next_queue.get_queue();
+
+        assert!(gamestate.hold());
+        assert_eq!(
+            gamestate.game.tetrimino.tetrimino_type(),
+            queue[0].tetrimino_type()
+        );
+        assert_eq!(
+            gamestate.hold.as_ref().map(Tetrimino::tetrimino_type),
+            Some(first)
+        );
+        assert_eq!(gamestate.next_queue.get_queue()[0], queue[1]);
+    }
+
+    #[test]
+    fn held_tetriminos_return_in_spawn_orientation() {
+        let mut gamestate = gamestate_with_t(Rules::default());
+        assert!(gamestate.apply_movement(Movement::Rotate(RotationDirection::Clockwise)));
+        assert!(gamestate.apply_movement(Movement::Left));
+
+        let spawned = Tetrimino::new(TetriminoType::T, RotationSystemKind::Srs);
+        assert!(gamestate.hold());
+        assert_eq!(gamestate.hold, Some(spawned.to_owned()));
+
+        // swap it back in after the next lock
+        gamestate.hard_drop();
+        assert!(gamestate.hold());
+        let tetrimino = &gamestate.game.tetrimino;
+        assert_eq!(tetrimino.tetrimino_type(), TetriminoType::T);
+        assert_eq!(tetrimino.facing(), spawned.facing());
+        let (col, row) = spawned.position();
+        assert_eq!(tetrimino.position(), (col, row - 1));
+    }
+
+    #[test]
+    fn hold_is_used_once_per_lock() {
+        let mut gamestate = GameState::new(Rules::default(), 1);
+
+        assert!(gamestate.hold());
+        assert!(gamestate.hold_used);
+        let current = gamestate.game.tetrimino.to_owned();
+        let held = gamestate.hold.to_owned();
+        assert!(!gamestate.hold());
+        assert_eq!(gamestate.game.tetrimino, current);
+        assert_eq!(gamestate.hold, held);
+
+        gamestate.hard_drop();
+        assert_eq!(gamestate.game_over, None);
+        assert!(!gamestate.hold_used);
+        assert!(gamestate.hold());
+        assert_eq!(
+            gamestate.game.tetrimino.tetrimino_type(),
+            held.unwrap().tetrimino_type()
+        );
+    }
+
+    #[test]
+    fn hold_does_nothing_while_paused_or_after_game_over() {
+        let mut gamestate = GameState::new(Rules::default(), 1);
+        gamestate.pause();
+        let paused = gamestate.to_owned();
+        assert!(!gamestate.hold());
+        assert_eq!(gamestate, paused);
+
+        gamestate.resume();
+        gamestate.end_game(GameOverReason::BlockOut);
+        let over = gamestate.to_owned();
+        assert!(!gamestate.hold());
+        assert_eq!(gamestate, over);
     }
 }
@@ -1,24 +1,11 @@
 use std::{
-    sync::mpsc::{self, Receiver},
+    sync::mpsc::{self, Receiver, Sender},
     thread::{self},
 };
 
-use crossterm::event::{self, KeyCode};
+use crossterm::event::{self, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Movement {
-    Rotate(RotationDirection),
-    Left,
-    Right,
-    Down,
-    Drop,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum RotationDirection {
-    Clockwise,
-    Counterclockwise,
-}
+use console_tetris::game::Movement;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Message {
@@ -28,31 +15,74 @@ pub enum Message {
     Move(Movement),
     /// New Tetrimino
     NewTetrimino,
+    /// Swap the current Tetrimino into the hold slot
+    Hold,
+    /// Show or hide the ghost piece
+    ToggleGhost,
+    /// The screen needs to be redrawn
+    Redraw,
+    /// Stop the process until it is continued (Ctrl-Z)
+    Suspend,
+    /// The process was continued after being stopped
+    Resume,
+    /// Pause the game and open the pause menu
+    Pause,
+    /// A key press to be interpreted by whatever screen is open
+    Key(KeyEvent),
 }
 
 pub fn start_io_handler() -> Receiver<Message> {
     let (io_tx, io_rx) = mpsc::channel();
+    start_signal_handler(io_tx.clone());
     thread::spawn(move || loop {
         let _ = io_tx.send(match event::read() {
-            Ok(event::Event::Key(key)) => match key.code {
-                KeyCode::Char(c) => match c {
-                    'q' => {
-                        let _ = io_tx.send(Message::QuitGame);
-                        break;
-                    }
-                    'n' => Message::NewTetrimino,
-                    'z' => Message::Move(Movement::Rotate(RotationDirection::Counterclockwise)),
-                    _ => continue,
-                },
-                KeyCode::Up => Message::Move(Movement::Rotate(RotationDirection::Clockwise)),
-                KeyCode::Left => Message::Move(Movement::Left),
-                KeyCode::Right => Message::Move(Movement::Right),
-                KeyCode::Down => Message::Move(Movement::Down),
-                KeyCode::Enter => Message::Move(Movement::Drop),
-                _ => continue,
-            },
+            // raw mode delivers Ctrl-C and Ctrl-Z as keys instead of signals,
+            // any other Ctrl key is left to the keymap
+            Ok(event::Event::Key(key)) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                match (key.code, key.kind) {
+                    (KeyCode::Char('c'), KeyEventKind::Press) => Message::QuitGame,
+                    (KeyCode::Char('z'), KeyEventKind::Press) => Message::Suspend,
+                    (KeyCode::Char('c' | 'z'), _) => continue,
+                    _ => Message::Key(key),
+                }
+            }
+            Ok(event::Event::Key(key)) => Message::Key(key),
+            Ok(event::Event::Resize(_, _)) => Message::Redraw,
             _ => continue,
         });
     });
     io_rx
 }
+
+/// Forward process signals to the game as [`Message`]s
+///
+/// `SIGINT`, `SIGTERM` and `SIGHUP` quit the game, `SIGTSTP` suspends it
+/// and `SIGCONT` resumes it
+#[cfg(unix)]
+fn start_signal_handler(io_tx: Sender<Message>) {
+    use signal_hook::{
+        consts::{SIGCONT, SIGHUP, SIGINT, SIGTERM, SIGTSTP},
+        iterator::Signals,
+    };
+
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP, SIGTSTP, SIGCONT])
+        .expect("Error setting signal handlers");
+
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            let _ = io_tx.send(match signal {
+                SIGTSTP => Message::Suspend,
+                SIGCONT => Message::Resume,
+                _ => Message::QuitGame,
+            });
+        }
+    });
+}
+
+#[cfg(not(unix))]
+fn start_signal_handler(io_tx: Sender<Message>) {
+    ctrlc::set_handler(move || {
+        let _ = io_tx.send(Message::QuitGame);
+    })
+    .expect("Error setting Ctrl-C handler");
+}
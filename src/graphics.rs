@@ -1,12 +1,71 @@
-use ratatui::{style::Color, widgets::canvas::Shape};
+use ratatui::{
+    prelude::{Alignment, Constraint, Direction, Layout},
+    style::Color,
+    text::Line,
+    widgets::{
+        block::{Position, Title},
+        canvas::{Canvas, Shape},
+        Block, Borders, Paragraph, StatefulWidget, Widget, Wrap,
+    },
+};
 
-use crate::{
-    game::{Game, NextQueue},
-    matrix::{MinoGrid, MATRIX_HEIGHT, MATRIX_WIDTH, PREVIEW_MATRIX_WIDTH},
-    position_outside_render_bounds,
-    tetramino::{Mino, Tetrimino, TetriminoPreview},
+use console_tetris::{
+    game::{Game, GameState, NextQueue},
+    matrix::{MinoGrid, MATRIX_HEIGHT, MATRIX_WIDTH},
+    tetramino::{Mino, Tetrimino, TetriminoPreview, TetriminoType},
 };
 
+use crate::settings::{Settings, Side};
+
+pub const PREVIEW_MATRIX_WIDTH: u16 = 8;
+pub const HOLD_MATRIX_HEIGHT: u16 = 4;
+
+const I_COLOR: Color = Color::Indexed(51);
+const J_COLOR: Color = Color::Indexed(33);
+const L_COLOR: Color = Color::Indexed(208);
+const O_COLOR: Color = Color::Indexed(226);
+const S_COLOR: Color = Color::Indexed(40);
+const T_COLOR: Color = Color::Indexed(128);
+const Z_COLOR: Color = Color::Indexed(160);
+
+/// The color minos of `tetrimino_type` are drawn in
+pub fn color(tetrimino_type: TetriminoType) -> Color {
+    match tetrimino_type {
+        TetriminoType::O => O_COLOR,
+        TetriminoType::I => I_COLOR,
+        TetriminoType::T => T_COLOR,
+        TetriminoType::L => L_COLOR,
+        TetriminoType::J => J_COLOR,
+        TetriminoType::S => S_COLOR,
+        TetriminoType::Z => Z_COLOR,
+    }
+}
+
+/// Get the `(width, height, preview_width, horizontal margin)` required for the matrix
+///
+/// A panel of `preview_width` is placed on both sides of the matrix
+///
+/// Returns [`None`] if the screen is too small
+pub fn get_matrix_size(vw_width: u16, vw_height: u16) -> Option<(u16, u16, u16, u16)> {
+    let required_width = MATRIX_WIDTH * 2 + PREVIEW_MATRIX_WIDTH * 4 + 6;
+    let required_height = MATRIX_HEIGHT + 2;
+
+    if vw_width < required_width || vw_height < required_height {
+        return None;
+    }
+
+    // the largest scale that fits both dimensions
+    let scale = ((vw_height - 2) / MATRIX_HEIGHT).min((vw_width - 6) / (required_width - 6));
+
+    let board_height = MATRIX_HEIGHT * scale;
+    let board_width = MATRIX_WIDTH * 2 * scale;
+    let preview_width = PREVIEW_MATRIX_WIDTH * 2 * scale;
+
+    let margin = (vw_width - board_width - preview_width * 2 - 6) / 2;
+
+    Some((board_width + 2, board_height + 2, preview_width + 2, margin))
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RenderMino {
     col: i32,
@@ -18,7 +77,11 @@ pub struct RenderMino {
 
 impl Shape for RenderMino {
     fn draw(&self, painter: &mut ratatui::widgets::canvas::Painter) {
-        if position_outside_render_bounds!(self.col, self.row) {
+        if self.col < 0
+            || self.row < 0
+            || self.col as usize >= self.cols
+            || self.row as usize >= self.rows
+        {
             return;
         }
 
@@ -62,83 +125,337 @@ impl Shape for RenderMino {
     }
 }
 
+/// Draw `minos` in the colors of their Tetriminos, dimmed if `dim`
 fn draw_minos(
     painter: &mut ratatui::widgets::canvas::Painter,
-    minos: &Vec<Mino>,
+    minos: &[Mino],
     matrix_width: usize,
     matrix_height: usize,
+    dim: bool,
 ) {
     for mino in minos.iter().map(|mino| RenderMino {
         col: mino.col,
         row: mino.row,
         cols: matrix_width,
         rows: matrix_height,
-        color: mino.color,
+        color: if dim {
+            dimmed(color(mino.tetrimino_type))
+        } else {
+            color(mino.tetrimino_type)
+        },
     }) {
         mino.draw(painter);
     }
 }
 
-impl Shape for TetriminoPreview {
+/// Draw `preview` with its top-left corner at `(col, row)`, dimmed if `dim`
+///
+/// Preview panels are measured in half minos across so pieces of any width can be
+/// centered, each mino is `size` minos big
+fn draw_preview(
+    painter: &mut ratatui::widgets::canvas::Painter,
+    preview: &TetriminoPreview,
+    (col, row): (i32, i32),
+    size: i32,
+    dim: bool,
+    rows: usize,
+) {
+    let minos: Vec<Mino> = preview
+        .get_minos()
+        .iter()
+        .flat_map(|mino| {
+            (0..size * 2).flat_map(move |x| {
+                (0..size).map(move |y| Mino {
+                    col: col + mino.col * size * 2 + x,
+                    row: row + mino.row * size + y,
+                    tetrimino_type: mino.tetrimino_type,
+                })
+            })
+        })
+        .collect();
+
+    draw_minos(
+        painter,
+        &minos,
+        (PREVIEW_MATRIX_WIDTH * 2).into(),
+        rows,
+        dim,
+    );
+}
+
+/// The column that centers `preview` in a preview panel
+fn centered_col(preview: &TetriminoPreview, size: i32) -> i32 {
+    (PREVIEW_MATRIX_WIDTH as i32 * 2 - preview.width() * size * 2) / 2
+}
+
+/// Draw `minos` of the matrix, dimmed if `dim`
+///
+/// Matrix rows count up from the bottom, so they are flipped for the screen.
+/// Only the visible part of the matrix is drawn, the buffer zone stays hidden
+fn draw_matrix_minos(painter: &mut ratatui::widgets::canvas::Painter, minos: &[Mino], dim: bool) {
+    draw_minos(
+        painter,
+        &minos
+            .iter()
+            .map(|mino| Mino {
+                col: mino.col,
+                row: MATRIX_HEIGHT as i32 - mino.row - 1,
+                tetrimino_type: mino.tetrimino_type,
+            })
+            .collect::<Vec<_>>(),
+        MATRIX_WIDTH.into(),
+        MATRIX_HEIGHT.into(),
+        dim,
+    );
+}
+
+/// Get a darker version of `color`
+///
+/// Colors from the 6x6x6 cube and RGB colors have each component halved,
+/// anything else becomes [`Color::DarkGray`]
+pub fn dimmed(color: Color) -> Color {
+    match color {
+        Color::Indexed(index @ 16..=231) => {
+            let index = index - 16;
+            let (r, g, b) = (index / 36, (index / 6) % 6, index % 6);
+            Color::Indexed(16 + (r / 2) * 36 + (g / 2) * 6 + b / 2)
+        }
+        Color::Rgb(r, g, b) => Color::Rgb(r / 2, g / 2, b / 2),
+        _ => Color::DarkGray,
+    }
+}
+
+/// A Tetrimino drawn in a dimmed version of its color
+///
+/// Used to project where the current Tetrimino will land
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ghost(pub Tetrimino);
+
+impl Shape for Ghost {
     fn draw(&self, painter: &mut ratatui::widgets::canvas::Painter) {
-        draw_minos(
-            painter,
-            &self.get_minos(),
-            PREVIEW_MATRIX_WIDTH.into(),
-            MATRIX_HEIGHT.into(),
-        );
+        draw_matrix_minos(painter, &self.0.get_minos(), true);
     }
 }
 
-impl Shape for Tetrimino {
+/// The stack and the current Tetrimino
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Board<'a>(pub &'a Game);
+
+impl Shape for Board<'_> {
     fn draw(&self, painter: &mut ratatui::widgets::canvas::Painter) {
-        draw_minos(
-            painter,
-            &self
-                .get_minos()
-                .iter()
-                .map(|mino| Mino {
-                    col: mino.col,
-                    row: MATRIX_HEIGHT as i32 - mino.row - 1,
-                    color: mino.color,
-                })
-                .collect(),
-            MATRIX_WIDTH.into(),
-            MATRIX_HEIGHT.into(),
-        );
+        draw_matrix_minos(painter, &self.0.matrix.get_minos(), false);
+        draw_matrix_minos(painter, &self.0.tetrimino.get_minos(), false);
     }
 }
 
-impl Shape for Game {
+/// The first `count` Tetriminos of the next queue
+///
+/// The next one is drawn twice as large, every one is centered across the panel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Next<'a> {
+    pub queue: &'a NextQueue,
+    pub count: usize,
+}
+
+impl Shape for Next<'_> {
     fn draw(&self, painter: &mut ratatui::widgets::canvas::Painter) {
-        let (rows, cols) = self.matrix.size();
+        let previews: Vec<_> = self
+            .queue
+            .get_queue()
+            .iter()
+            .take(self.count)
+            .enumerate()
+            .map(|(index, tetrimino)| (tetrimino.preview(), if index == 0 { 2 } else { 1 }))
+            .collect();
 
-        draw_minos(
-            painter,
-            &self
-                .matrix
-                .get_minos()
-                .iter()
-                .map(|mino| Mino {
-                    col: mino.col,
-                    row: rows as i32 - mino.row - 1,
-                    color: mino.color,
-                })
-                .collect(),
-            cols,
-            rows,
-        );
+        // leave a row above every piece while there is room, the top one goes first
+        let height: i32 = previews
+            .iter()
+            .map(|(preview, size)| preview.height() * size)
+            .sum();
+        let mut spare = MATRIX_HEIGHT as i32 - height;
+        let mut row = 0;
 
-        self.tetrimino.draw(painter);
+        for (index, (preview, size)) in previews.iter().enumerate() {
+            if spare > 0 && (index > 0 || spare >= previews.len() as i32) {
+                row += 1;
+                spare -= 1;
+            }
+
+            draw_preview(
+                painter,
+                preview,
+                (centered_col(preview, *size), row),
+                *size,
+                false,
+                MATRIX_HEIGHT.into(),
+            );
+            row += preview.height() * size;
+        }
     }
 }
 
-impl Shape for NextQueue {
+/// The contents of the hold slot
+///
+/// Drawn dimmed while hold is unavailable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hold<'a> {
+    pub tetrimino: Option<&'a Tetrimino>,
+    pub used: bool,
+}
+
+impl Shape for Hold<'_> {
     fn draw(&self, painter: &mut ratatui::widgets::canvas::Painter) {
-        if let Some(tetraminos) = self.get_queue().chunks(6).next() {
-            for (index, tetramino) in tetraminos.iter().enumerate() {
-                tetramino.preview(index).draw(painter);
-            }
+        let Some(tetrimino) = self.tetrimino else {
+            return;
         };
+
+        let preview = tetrimino.preview();
+        draw_preview(
+            painter,
+            &preview,
+            (
+                centered_col(&preview, 1),
+                (HOLD_MATRIX_HEIGHT as i32 - preview.height()) / 2,
+            ),
+            1,
+            self.used,
+            HOLD_MATRIX_HEIGHT.into(),
+        );
+    }
+}
+
+/// The whole game: hold, stats, the matrix and the next queue
+pub struct Tetris<'a> {
+    pub settings: &'a Settings,
+}
+
+impl StatefulWidget for Tetris<'_> {
+    type State = GameState;
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State,
+    ) {
+        let Some((board_width, board_height, preview_width, margin)) =
+            get_matrix_size(area.width, area.height)
+        else {
+            Paragraph::new("This terminal is too small to play Tetris!").render(area, buf);
+            return;
+        };
+
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(board_height),
+                Constraint::Length(area.height - board_height),
+            ])
+            .split(area);
+
+        let layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Length(margin),
+                Constraint::Length(preview_width),
+                Constraint::Length(board_width),
+                Constraint::Length(preview_width),
+                Constraint::Length(margin),
+            ])
+            .split(vertical_layout[0]);
+
+        let scale = (board_height - 2) / MATRIX_HEIGHT;
+        let (side_area, next_area) = match self.settings.next_queue_side {
+            Side::Left => (layout[3], layout[1]),
+            Side::Right => (layout[1], layout[3]),
+        };
+        let side_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(scale * HOLD_MATRIX_HEIGHT + 2),
+                Constraint::Min(0),
+            ])
+            .split(side_area);
+
+        Canvas::default()
+            .block(Block::default().title("HOLD").borders(Borders::ALL))
+            .x_bounds([0.0, (PREVIEW_MATRIX_WIDTH * 2).into()])
+            .y_bounds([0.0, HOLD_MATRIX_HEIGHT.into()])
+            .marker(ratatui::symbols::Marker::Block)
+            .paint(|ctx| {
+                if state.paused() {
+                    return;
+                }
+                ctx.draw(&Hold {
+                    tetrimino: state.hold.as_ref(),
+                    used: state.hold_used,
+                })
+            })
+            .render(side_layout[0], buf);
+
+        let lines = match state.rules.mode.line_goal() {
+            Some(goal) => format!("{}/{goal}", state.scoring.lines),
+            None => state.scoring.lines.to_string(),
+        };
+        let mut stats = vec![
+            Line::from("SCORE"),
+            Line::from(state.scoring.score.to_string()),
+            Line::from(""),
+            Line::from("LINES"),
+            Line::from(lines),
+            Line::from(""),
+            Line::from("LEVEL"),
+            Line::from(state.scoring.level.to_string()),
+            Line::from(""),
+        ];
+        if let Some(callout) = &state.callout {
+            stats.extend(callout.lines().into_iter().map(Line::from));
+        }
+
+        Paragraph::new(stats)
+            .wrap(Wrap { trim: true })
+            .block(Block::default().title("STATS").borders(Borders::ALL))
+            .render(side_layout[1], buf);
+
+        Canvas::default()
+            .block(
+                Block::default()
+                    .title("TETRIS")
+                    .title(
+                        Title::from(format!("SEED {}", state.seed))
+                            .position(Position::Bottom)
+                            .alignment(Alignment::Right),
+                    )
+                    .borders(Borders::ALL),
+            )
+            .x_bounds([0.0, MATRIX_WIDTH.into()])
+            .y_bounds([0.0, MATRIX_HEIGHT.into()])
+            .marker(ratatui::symbols::Marker::Block)
+            .paint(|ctx| {
+                // nobody gets to scout the board while paused
+                if state.paused() {
+                    return;
+                }
+                if self.settings.ghost_piece {
+                    ctx.draw(&Ghost(state.game.ghost()));
+                }
+                ctx.draw(&Board(&state.game));
+            })
+            .render(layout[2], buf);
+
+        Canvas::default()
+            .block(Block::default().title("NEXT").borders(Borders::ALL))
+            .x_bounds([0.0, (PREVIEW_MATRIX_WIDTH * 2).into()])
+            .y_bounds([0.0, MATRIX_HEIGHT.into()])
+            .marker(ratatui::symbols::Marker::Block)
+            .paint(|ctx| {
+                if !state.paused() {
+                    ctx.draw(&Next {
+                        queue: &state.next_queue,
+                        count: self.settings.next_previews,
+                    });
+                }
+            })
+            .render(next_area, buf);
     }
 }
@@ -0,0 +1,358 @@
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    env, fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use console_tetris::{
+    game::GameState,
+    rules::{GameMode, Ranking, Rules},
+};
+
+use crate::{menu::format_time, saves};
+
+/// How many results are kept for each mode
+pub const MAX_HIGH_SCORES: usize = 10;
+/// The longest name a result can be saved under
+pub const MAX_NAME_LENGTH: usize = 10;
+
+/// One finished game on a high score table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+    pub lines: u32,
+    pub level: i32,
+    pub time: Duration,
+    /// When the game was played, in seconds since the Unix epoch
+    pub date: u64,
+    pub seed: u64,
+}
+
+impl HighScore {
+    /// The result of `gamestate`, played just now by `name`
+    pub fn new(name: &str, gamestate: &GameState) -> Self {
+        Self {
+            name: name.to_owned(),
+            score: gamestate.scoring.score,
+            lines: gamestate.scoring.lines,
+            level: gamestate.scoring.level,
+            time: gamestate.play_time(),
+            date: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            seed: gamestate.seed,
+        }
+    }
+
+    /// Whether the result is worth a place on the table of `mode`
+    ///
+    /// Games ranked by time only count if they reached the line goal
+    fn qualifies(&self, mode: GameMode) -> bool {
+        match mode.ranking() {
+            Ranking::Score => self.score > 0,
+            Ranking::Time => mode.line_goal().is_some_and(|goal| self.lines >= goal),
+        }
+    }
+
+    /// Compare two results, the better one first
+    ///
+    /// Ties go to the earlier result
+    fn compare(&self, other: &Self, ranking: Ranking) -> Ordering {
+        let ranked = match ranking {
+            Ranking::Score => other.score.cmp(&self.score),
+            Ranking::Time => self.time.cmp(&other.time),
+        };
+        ranked.then(self.date.cmp(&other.date))
+    }
+
+    /// A row of the high score table, lined up with [`HighScores::header`]
+    ///
+    /// `rank` counts from 1
+    pub fn row(&self, rank: usize) -> String {
+        format!(
+            "{rank:>2}. {:<MAX_NAME_LENGTH$} {:>8} {:>5} {:>3} {:>8} {}",
+            self.name,
+            self.score,
+            self.lines,
+            self.level,
+            format_time(self.time),
+            format_date(self.date),
+        )
+    }
+}
+
+/// The best results of every [`GameMode`]
+///
+/// Stored in `high_scores.toml` in the data directory
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct HighScores {
+    modes: BTreeMap<GameMode, Vec<HighScore>>,
+}
+
+impl HighScores {
+    /// The column names lined up with [`HighScore::row`]
+    pub fn header() -> String {
+        format!(
+            "    {:<MAX_NAME_LENGTH$} {:>8} {:>5} {:>3} {:>8} {}",
+            "Name", "Score", "Lines", "Lv", "Time", "Date"
+        )
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("console_tetris").join("high_scores.toml"))
+    }
+
+    /// Load the saved high scores, or empty tables if none were saved
+    ///
+    /// High scores that can't be read are set aside, see [`saves::set_aside`]
+    pub fn load() -> Result<Self, io::Error> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err),
+        };
+
+        toml::from_str(&contents).map_err(|err| saves::set_aside(&path, err))
+    }
+
+    /// Save the high scores for the next launch
+    pub fn save(&self) -> Result<(), io::Error> {
+        let Some(path) = Self::path() else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no data directory"));
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let contents =
+            toml::to_string(self).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, contents)
+    }
+
+    /// The results of `mode`, best first
+    pub fn get(&self, mode: GameMode) -> &[HighScore] {
+        self.modes.get(&mode).map_or(&[], Vec::as_slice)
+    }
+
+    /// The index `high_score` would take on the table of `mode`,
+    /// or [`None`] if it doesn't make the table
+    pub fn rank(&self, mode: GameMode, high_score: &HighScore) -> Option<usize> {
+        if !high_score.qualifies(mode) {
+            return None;
+        }
+
+        let ranking = mode.ranking();
+        let rank = self
+            .get(mode)
+            .iter()
+            .take_while(|entry| entry.compare(high_score, ranking).is_le())
+            .count();
+        (rank < MAX_HIGH_SCORES).then_some(rank)
+    }
+
+    /// Put `high_score` on the table of `mode`, dropping the worst result if it is full
+    ///
+    /// Returns the index it took, see [`HighScores::rank`]
+    pub fn insert(&mut self, mode: GameMode, high_score: HighScore) -> Option<usize> {
+        let rank = self.rank(mode, &high_score)?;
+
+        let entries = self.modes.entry(mode).or_default();
+        entries.insert(rank, high_score);
+        entries.truncate(MAX_HIGH_SCORES);
+        Some(rank)
+    }
+}
+
+/// Whether `gamestate` was played by the rules of its mode and can go on its table
+///
+/// Games with a different randomizer or rotation system chosen in the settings aren't ranked
+pub fn ranked(gamestate: &GameState) -> bool {
+    gamestate.rules == Rules::from(gamestate.rules.mode)
+}
+
+/// The name to suggest for a new high score, the name of the logged in user
+pub fn default_name() -> String {
+    let name = env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "Player".to_owned());
+    name.chars().take(MAX_NAME_LENGTH).collect()
+}
+
+/// Format seconds since the Unix epoch as a `year-month-day` UTC date
+fn format_date(secs: u64) -> String {
+    // days to civil date, from Howard Hinnant's date algorithms
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{year}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use console_tetris::{randomizer::RandomizerKind, rotation::RotationSystemKind};
+
+    use super::*;
+
+    /// A result with `score` in `secs` seconds, `lines` cleared, played at `date`
+    fn result(score: u32, secs: u64, lines: u32, date: u64) -> HighScore {
+        HighScore {
+            name: format!("p{date}"),
+            score,
+            lines,
+            level: 1,
+            time: Duration::from_secs(secs),
+            date,
+            seed: date,
+        }
+    }
+
+    fn dates(high_scores: &HighScores, mode: GameMode) -> Vec<u64> {
+        high_scores
+            .get(mode)
+            .iter()
+            .map(|entry| entry.date)
+            .collect()
+    }
+
+    #[test]
+    fn score_tables_rank_the_highest_score_first() {
+        let mut high_scores = HighScores::default();
+
+        assert_eq!(
+            high_scores.insert(GameMode::Marathon, result(100, 60, 5, 1)),
+            Some(0)
+        );
+        assert_eq!(
+            high_scores.insert(GameMode::Marathon, result(300, 60, 5, 2)),
+            Some(0)
+        );
+        assert_eq!(
+            high_scores.insert(GameMode::Marathon, result(200, 60, 5, 3)),
+            Some(1)
+        );
+        // a tie goes after the earlier result
+        assert_eq!(
+            high_scores.insert(GameMode::Marathon, result(200, 10, 5, 4)),
+            Some(2)
+        );
+        assert_eq!(
+            high_scores.insert(GameMode::Marathon, result(0, 60, 0, 5)),
+            None
+        );
+
+        assert_eq!(dates(&high_scores, GameMode::Marathon), vec![2, 3, 4, 1]);
+        assert!(high_scores.get(GameMode::Classic).is_empty());
+    }
+
+    #[test]
+    fn time_tables_rank_the_fastest_finished_game_first() {
+        let mut high_scores = HighScores::default();
+        let goal = GameMode::Sprint
+            .line_goal()
+            .expect("sprint has a line goal");
+
+        assert_eq!(
+            high_scores.insert(GameMode::Sprint, result(0, 90, goal, 1)),
+            Some(0)
+        );
+        assert_eq!(
+            high_scores.insert(GameMode::Sprint, result(0, 60, goal, 2)),
+            Some(0)
+        );
+        assert_eq!(
+            high_scores.insert(GameMode::Sprint, result(900, 75, goal, 3)),
+            Some(1)
+        );
+        // a tie goes after the earlier result, whatever the score
+        assert_eq!(
+            high_scores.insert(GameMode::Sprint, result(5000, 75, goal, 4)),
+            Some(2)
+        );
+        // a game that topped out before the goal doesn't count, however fast
+        assert_eq!(
+            high_scores.insert(GameMode::Sprint, result(9000, 5, goal - 1, 5)),
+            None
+        );
+
+        assert_eq!(dates(&high_scores, GameMode::Sprint), vec![2, 3, 4, 1]);
+    }
+
+    #[test]
+    fn full_tables_drop_the_worst_result() {
+        let mut high_scores = HighScores::default();
+        for date in 1..=MAX_HIGH_SCORES as u64 + 2 {
+            high_scores.insert(GameMode::Marathon, result(date as u32 * 100, 60, 5, date));
+        }
+
+        let kept: Vec<u64> = (3..=MAX_HIGH_SCORES as u64 + 2).rev().collect();
+        assert_eq!(dates(&high_scores, GameMode::Marathon), kept);
+
+        // equal to the worst on the table is not enough to make it
+        let worst = result(300, 60, 5, 100);
+        assert_eq!(high_scores.rank(GameMode::Marathon, &worst), None);
+        assert_eq!(high_scores.insert(GameMode::Marathon, worst), None);
+
+        assert_eq!(
+            high_scores.insert(GameMode::Marathon, result(350, 60, 5, 101)),
+            Some(9)
+        );
+        assert_eq!(high_scores.get(GameMode::Marathon).len(), MAX_HIGH_SCORES);
+        assert_eq!(high_scores.get(GameMode::Marathon)[9].date, 101);
+    }
+
+    #[test]
+    fn only_games_with_the_rules_of_their_mode_are_ranked() {
+        for mode in GameMode::ALL {
+            assert!(ranked(&GameState::new(Rules::from(mode), 1)));
+
+            let randomizer = Rules {
+                randomizer: RandomizerKind::PureRandom,
+                ..Rules::from(mode)
+            };
+            assert!(!ranked(&GameState::new(randomizer, 1)));
+
+            let rotation_system = Rules {
+                rotation_system: RotationSystemKind::NoKicks,
+                ..Rules::from(mode)
+            };
+            assert!(!ranked(&GameState::new(rotation_system, 1)));
+        }
+    }
+
+    #[test]
+    fn high_scores_round_trip() {
+        let mut high_scores = HighScores::default();
+        let goal = GameMode::Sprint
+            .line_goal()
+            .expect("sprint has a line goal");
+        high_scores.insert(GameMode::Marathon, result(1234, 321, 12, 1_700_000_000));
+        high_scores.insert(GameMode::Sprint, result(4321, 95, goal, 1_700_000_001));
+
+        let contents = toml::to_string(&high_scores).expect("high scores can be saved");
+        let loaded: HighScores = toml::from_str(&contents).expect("high scores can be loaded");
+        assert_eq!(loaded, high_scores);
+    }
+}
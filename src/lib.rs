@@ -0,0 +1,16 @@
+//! The rules of Tetris, with no terminal attached
+//!
+//! Drive a [`game::GameState`] with [`replay::Input`]s and one call to
+//! [`game::GameState::step`] per frame, and read the matrix back from
+//! [`game::GameState::game`]. Cells hold the [`tetramino::TetriminoType`] that
+//! filled them, how they look is up to the frontend.
+
+pub mod clock;
+pub mod game;
+pub mod matrix;
+pub mod randomizer;
+pub mod replay;
+pub mod rotation;
+pub mod rules;
+pub mod scoring;
+pub mod tetramino;
@@ -1,83 +1,239 @@
-mod game;
+mod app;
+mod args;
+mod auto_repeat;
+mod controls;
 mod game_handler;
 mod graphics;
-mod matrix;
-mod tetramino;
+mod high_scores;
+mod menu;
+mod replays;
+mod saves;
+mod settings;
 
+use app::App;
+use args::{Args, USAGE};
+use console_tetris::{clock::FRAME_TIME, game::GameState, replay::Replay};
+use controls::Keymap;
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture},
+    cursor::{Hide, Show},
+    event::{
+        DisableMouseCapture, EnableMouseCapture, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
 };
-use game::{GameState, Tetris};
 use game_handler::{start_io_handler, Message};
+use high_scores::HighScores;
+use menu::Menu;
 use ratatui::{
     prelude::{Backend, CrosstermBackend},
     Terminal,
 };
-use std::{io, panic};
+use replays::ReplayViewer;
+use std::{
+    env, io, panic, process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::RecvTimeoutError,
+    },
+    time::Instant,
+};
 
 fn main() -> Result<(), io::Error> {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{USAGE}");
+        return Ok(());
+    }
+
     // emergency handlers
-    ctrlc::set_handler(|| println!("no")).expect("Error setting Ctrl-C handler");
     let old_panic = panic::take_hook();
     panic::set_hook(Box::new(move |v| {
-        let _ = disable_raw_mode();
-        let mut stdout = io::stdout();
-        let _ = execute!(stdout, LeaveAlternateScreen, DisableMouseCapture);
+        let _ = leave_terminal();
         old_panic(v);
     }));
 
+    // read the config before taking over the terminal so errors can be seen
+    let keymap = Keymap::load()?;
+    let replay = args.replay.as_deref().map(replays::load).transpose()?;
+    // a save or high scores that can't be loaded are not worth refusing to start over
+    let saved_game = saves::load().unwrap_or_else(|err| {
+        eprintln!("Could not load the saved game: {err}");
+        None
+    });
+    let high_scores = HighScores::load().unwrap_or_else(|err| {
+        eprintln!("Could not load the high scores: {err}");
+        HighScores::default()
+    });
+
     // create term
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    enter_terminal()?;
+
+    // ask for key release events, needed to tell when a key is held
+    if supports_keyboard_enhancement().unwrap_or(false) {
+        KEYBOARD_ENHANCEMENT.store(true, Ordering::SeqCst);
+        execute!(
+            io::stdout(),
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+
+    let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
 
     // run the game
-    game_loop(&mut terminal)?;
+    let result = game_loop(
+        &mut terminal,
+        keymap,
+        args.seed,
+        replay,
+        saved_game,
+        high_scores,
+    );
 
     // cleanup term
+    leave_terminal()?;
+
+    result
+}
+
+/// Whether the terminal has been asked to report key releases
+static KEYBOARD_ENHANCEMENT: AtomicBool = AtomicBool::new(false);
+
+/// Put the terminal into the state the game is drawn in
+fn enter_terminal() -> Result<(), io::Error> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture, Hide)?;
+    if KEYBOARD_ENHANCEMENT.load(Ordering::SeqCst) {
+        execute!(
+            io::stdout(),
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+    Ok(())
+}
+
+/// Return the terminal to the state the shell expects
+fn leave_terminal() -> Result<(), io::Error> {
+    if KEYBOARD_ENHANCEMENT.load(Ordering::SeqCst) {
+        execute!(io::stdout(), PopKeyboardEnhancementFlags)?;
+    }
     disable_raw_mode()?;
     execute!(
-        terminal.backend_mut(),
+        io::stdout(),
         LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+        DisableMouseCapture,
+        Show
+    )
+}
+
+/// Restore the terminal and stop the process until it is continued
+#[cfg(unix)]
+fn suspend() -> Result<(), io::Error> {
+    leave_terminal()?;
+    signal_hook::low_level::emulate_default_handler(signal_hook::consts::SIGTSTP)?;
+    enter_terminal()
+}
 
+#[cfg(not(unix))]
+fn suspend() -> Result<(), io::Error> {
     Ok(())
 }
 
-fn game_loop(terminal: &mut Terminal<impl Backend>) -> Result<(), io::Error> {
-    let mut gamestate = GameState::default();
+fn game_loop(
+    terminal: &mut Terminal<impl Backend>,
+    keymap: Keymap,
+    seed: Option<u64>,
+    replay: Option<Replay>,
+    saved_game: Option<GameState>,
+    high_scores: HighScores,
+) -> Result<(), io::Error> {
+    let mut app = App {
+        keymap,
+        seed,
+        // Windows always reports key releases
+        key_releases: cfg!(windows) || KEYBOARD_ENHANCEMENT.load(Ordering::SeqCst),
+        menus: vec![Menu::main(saved_game.is_some())],
+        saved_game,
+        high_scores,
+        ..App::default()
+    };
+    if let Some(replay) = replay {
+        app.viewer = Some(ReplayViewer::new(replay));
+        app.menus.clear();
+    }
 
     let io_rx = start_io_handler();
 
-    loop {
-        match io_rx.try_recv() {
-            Ok(v) => match v {
-                Message::QuitGame => break,
-                Message::Move(control) => {
-                    gamestate.game.apply_movement(control);
-                }
-                Message::NewTetrimino => {
-                    gamestate.game.new_tetrimino(gamestate.next_queue.next());
+    // whether the screen is out of date
+    let mut dirty = true;
+    let mut last_draw: Option<Instant> = None;
+
+    while !app.quit {
+        let now = Instant::now();
+        let next_frame = last_draw.map_or(now, |last_draw| last_draw + FRAME_TIME);
+
+        if dirty && now >= next_frame {
+            terminal.draw(|f| app.draw(f))?;
+            dirty = false;
+            last_draw = Some(now);
+        }
+
+        // sleep until there is input, the game has work to do or a pending redraw is due
+        let deadline = match (app.next_deadline().map(|wait| now + wait), dirty) {
+            (Some(deadline), true) => Some(deadline.min(next_frame)),
+            (None, true) => Some(next_frame),
+            (deadline, false) => deadline,
+        };
+
+        let message = match deadline {
+            Some(deadline) => io_rx.recv_timeout(deadline.saturating_duration_since(now)),
+            None => io_rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match message {
+            Ok(message) => {
+                dirty = true;
+                // catch up first, so the input lands on the frame it arrived on
+                app.tick();
+                match message {
+                    Message::Suspend => {
+                        app.handle_message(Message::Pause);
+                        suspend()?;
+                        terminal.clear()?;
+                    }
+                    Message::Resume => {
+                        // suspend() has already entered the terminal again,
+                        // the screen may have been drawn over while stopped
+                        terminal.clear()?;
+                    }
+                    message => app.handle_message(message),
                 }
-            },
-            Err(_) => (),
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => break,
         };
 
-        if !gamestate.tick() {
-            gamestate.game_over = true;
-            break;
+        if app.next_deadline().is_some_and(|wait| wait.is_zero()) {
+            dirty = true;
         }
 
-        terminal.draw(|f| {
-            f.render_stateful_widget(Tetris {}, f.size(), &mut gamestate);
-        })?;
+        app.tick();
     }
 
-    Ok(())
+    // keep a game quit halfway through to be continued on the next launch
+    match app.unfinished_game() {
+        Some(gamestate) => saves::save(gamestate),
+        None => Ok(()),
+    }
 }
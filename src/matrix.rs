@@ -1,19 +1,20 @@
 use grid::Grid;
-use ratatui::style::Color;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    game_handler::RotationDirection,
-    tetramino::{Facing, Mino},
+    rotation::RotationDirection,
+    tetramino::{Facing, Mino, TetriminoType},
 };
 
 pub const MATRIX_WIDTH: u16 = 10;
 pub const MATRIX_HEIGHT: u16 = 20;
-
-pub const PREVIEW_MATRIX_WIDTH: u16 = 6;
+/// Hidden rows above the visible matrix that Tetriminos spawn into
+pub const MATRIX_BUFFER_HEIGHT: u16 = 20;
 
 /// Check if `x` is outside the left bound of the matrix
 ///
-/// ```
+/// ```ignore
 /// let x: i32 = ..;
 /// if x_position_outside_left_bound!(x) ..
 /// ```
@@ -26,7 +27,7 @@ macro_rules! x_position_outside_left_bound {
 
 /// Check if `x` is outside the right bound of the matrix
 ///
-/// ```
+/// ```ignore
 /// let x: i32 = ..;
 /// if x_position_outside_right_bound!(x) ..
 /// ```
@@ -39,7 +40,7 @@ macro_rules! x_position_outside_right_bound {
 
 /// Check if `x` is outside the bounds of the matrix
 ///
-/// ```
+/// ```ignore
 /// let x: i32 = ..;
 /// if x_position_outside_bounds!(x) ..
 /// ```
@@ -52,7 +53,7 @@ macro_rules! x_position_outside_bounds {
 
 /// Check if `y` is outside the bottom bound of the matrix
 ///
-/// ```
+/// ```ignore
 /// let y: i32 = ..;
 /// if y_position_outside_bottom_bound!(y) ..
 /// ```
@@ -65,7 +66,7 @@ macro_rules! y_position_outside_bottom_bound {
 
 /// Check if `y` is outside the top bound of the matrix
 ///
-/// ```
+/// ```ignore
 /// let y: i32 = ..;
 /// if y_position_outside_top_bound!(y) ..
 /// ```
@@ -78,7 +79,7 @@ macro_rules! y_position_outside_top_bound {
 
 /// Check if `y` is outside the bounds of the matrix
 ///
-/// ```
+/// ```ignore
 /// let y: i32 = ..;
 /// if y_position_outside_bounds!(y) ..
 /// ```
@@ -91,7 +92,7 @@ macro_rules! y_position_outside_bounds {
 
 /// Check if `x` and `y` are outside the bounds of the matrix (excluding the top)
 ///
-/// ```
+/// ```ignore
 /// let x: i32 = ..;
 /// let y: i32 = ..;
 /// if position_outside_bounds!(x, y) ..
@@ -105,7 +106,7 @@ macro_rules! position_outside_bounds {
 
 /// Check if `x` and `y` are outside the bounds of the matrix
 ///
-/// ```
+/// ```ignore
 /// let x: i32 = ..;
 /// let y: i32 = ..;
 /// if position_outside_render_bounds!(x, y) ..
@@ -117,33 +118,13 @@ macro_rules! position_outside_render_bounds {
     };
 }
 
-/// Get the `(width, height, preview_width, horizontal margin)` required for the matrix
-///
-/// Returns [`None`] if the screen is too small
-pub fn get_matrix_size(vw_width: u16, vw_height: u16) -> Option<(u16, u16, u16, u16)> {
-    let required_width = MATRIX_WIDTH * 2 + PREVIEW_MATRIX_WIDTH * 2 + 4;
-    let required_height = MATRIX_HEIGHT + 2;
-
-    if vw_width < required_width || vw_height < required_height {
-        return None;
-    }
-
-    let canvas_height = vw_height - 2;
-    let board_height = canvas_height - (canvas_height % MATRIX_HEIGHT);
-
-    let board_width = (board_height * MATRIX_WIDTH / MATRIX_HEIGHT) * 2;
-    let preview_width = (board_height * PREVIEW_MATRIX_WIDTH / MATRIX_HEIGHT) * 2;
-
-    let margin = (vw_width - board_width - preview_width) / 2;
-
-    Some((board_width + 2, board_height + 2, preview_width + 2, margin))
-}
-
 /// Get the spawn point of a tetramino
 ///
+/// Tetriminos spawn in the two rows just above the visible matrix
+///
 /// Returns `(x, y)`
 pub fn get_spawn_point() -> (i32, i32) {
-    (2, (MATRIX_HEIGHT - 1).into())
+    (2, (MATRIX_HEIGHT + 2).into())
 }
 
 pub trait MinoGrid {
@@ -155,11 +136,17 @@ pub trait GridRotation {
     fn rotated(&self, direction: RotationDirection) -> Self;
 }
 
+/// Saved as a list of rows, see [`SavedMatrix`]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "SavedMatrix", into = "SavedMatrix")
+)]
 pub struct Matrix {
     rows: usize,
     cols: usize,
-    matrix: Grid<Option<Color>>,
+    matrix: Grid<Option<TetriminoType>>,
     pub rotation: Facing,
 }
 
@@ -180,30 +167,113 @@ impl Matrix {
 
     pub fn get_mino(&self, row: usize, col: usize) -> Option<Mino> {
         match self.matrix.get(row, col) {
-            Some(mino) => mino.map(|color| Mino {
+            Some(mino) => mino.map(|tetrimino_type| Mino {
                 col: col as i32,
                 row: row as i32,
-                color: color,
+                tetrimino_type,
             }),
             None => None,
         }
     }
 
-    pub fn get_matrix(&self) -> Grid<Option<Color>> {
+    pub fn get_matrix(&self) -> Grid<Option<TetriminoType>> {
         self.matrix.to_owned()
     }
 
     pub fn set_mino(&mut self, mino: Mino) {
-        if position_outside_render_bounds!(mino.col, mino.row) {
+        if position_outside_bounds!(mino.col, mino.row) || mino.row as usize >= self.rows {
             return;
         }
 
-        self.matrix[mino.row as usize][mino.col as usize] = Some(mino.color);
+        self.matrix[mino.row as usize][mino.col as usize] = Some(mino.tetrimino_type);
+    }
+
+    /// Check if every cell of `row` is filled
+    pub fn row_complete(&self, row: usize) -> bool {
+        (0..self.cols).all(|col| self.get_mino(row, col).is_some())
+    }
+
+    /// Remove all completed rows, shifting every row above them down
+    ///
+    /// Returns the number of lines cleared
+    pub fn clear_lines(&mut self) -> usize {
+        let complete_rows: Vec<usize> = (0..self.rows)
+            .filter(|row| self.row_complete(*row))
+            .collect();
+
+        // remove from the top down so the remaining indices stay valid
+        for row in complete_rows.iter().rev() {
+            // an empty row enters at the top of the matrix
+            self.matrix.push_row(vec![None; self.cols]);
+            self.matrix.remove_row(*row);
+        }
+
+        complete_rows.len()
     }
 }
 
-impl From<Grid<Option<Color>>> for Matrix {
-    fn from(value: Grid<Option<Color>>) -> Self {
+/// How a [`Matrix`] is saved
+///
+/// Every row is a string with the letter of the Tetrimino filling each cell
+/// or `.` for an empty cell, in the order the rows are stored
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SavedMatrix {
+    rows: Vec<String>,
+    rotation: Facing,
+}
+
+#[cfg(feature = "serde")]
+impl From<Matrix> for SavedMatrix {
+    fn from(matrix: Matrix) -> Self {
+        Self {
+            rows: matrix
+                .matrix
+                .iter_rows()
+                .map(|row| {
+                    row.map(|cell| cell.map_or('.', TetriminoType::letter))
+                        .collect()
+                })
+                .collect(),
+            rotation: matrix.rotation,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SavedMatrix> for Matrix {
+    type Error = String;
+
+    fn try_from(saved: SavedMatrix) -> Result<Self, Self::Error> {
+        let cols = saved.rows.first().map_or(0, |row| row.chars().count());
+        if cols == 0 {
+            return Err("empty matrix".to_owned());
+        }
+
+        let mut cells = vec![];
+        for row in &saved.rows {
+            if row.chars().count() != cols {
+                return Err("rows of different lengths".to_owned());
+            }
+            for cell in row.chars() {
+                cells.push(match cell {
+                    '.' => None,
+                    letter => Some(
+                        TetriminoType::from_letter(letter)
+                            .ok_or_else(|| format!("unknown Tetrimino `{letter}`"))?,
+                    ),
+                });
+            }
+        }
+
+        let mut matrix = Matrix::from(Grid::from_vec(cells, cols));
+        matrix.rotation = saved.rotation;
+        Ok(matrix)
+    }
+}
+
+impl From<Grid<Option<TetriminoType>>> for Matrix {
+    fn from(value: Grid<Option<TetriminoType>>) -> Self {
         Self {
             rows: value.rows(),
             cols: value.cols(),
@@ -220,14 +290,11 @@ impl MinoGrid for Matrix {
             .enumerate()
             .flat_map(|(row, row_iter)| {
                 row_iter.enumerate().filter_map(move |(col, tile)| {
-                    return match tile {
-                        Some(color) => Some(Mino {
-                            col: col as i32,
-                            row: row as i32,
-                            color: *color,
-                        }),
-                        None => None,
-                    };
+                    tile.map(|tetrimino_type| Mino {
+                        col: col as i32,
+                        row: row as i32,
+                        tetrimino_type,
+                    })
                 })
             })
             .collect()
@@ -237,7 +304,10 @@ impl MinoGrid for Matrix {
 impl GridRotation for Matrix {
     fn rotated(&self, direction: RotationDirection) -> Self {
         let (rows, cols) = self.size();
-        let mut rotated = Matrix::new(cols, rows, self.rotation.rotated(direction));
+        let mut rotated = match direction {
+            RotationDirection::Half => Matrix::new(rows, cols, self.rotation.rotated(direction)),
+            _ => Matrix::new(cols, rows, self.rotation.rotated(direction)),
+        };
 
         // map the original minos to the new rotated grid
         for (row, row_iter) in self.matrix.iter_rows().enumerate() {
@@ -245,9 +315,11 @@ impl GridRotation for Matrix {
                 rotated.matrix[match direction {
                     RotationDirection::Clockwise => col,
                     RotationDirection::Counterclockwise => cols - col - 1,
+                    RotationDirection::Half => rows - row - 1,
                 }][match direction {
                     RotationDirection::Clockwise => rows - row - 1,
                     RotationDirection::Counterclockwise => row,
+                    RotationDirection::Half => cols - col - 1,
                 }] = *mino
             }
         }
@@ -255,3 +327,71 @@ impl GridRotation for Matrix {
         rotated
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill_row(matrix: &mut Matrix, row: i32) {
+        for col in 0..MATRIX_WIDTH as i32 {
+            set(matrix, row, col, TetriminoType::I);
+        }
+    }
+
+    fn set(matrix: &mut Matrix, row: i32, col: i32, tetrimino_type: TetriminoType) {
+        matrix.set_mino(Mino {
+            col,
+            row,
+            tetrimino_type,
+        });
+    }
+
+    #[test]
+    fn clear_lines_shifts_rows_above_non_adjacent_lines() {
+        let mut matrix = Matrix::new(MATRIX_HEIGHT.into(), MATRIX_WIDTH.into(), Facing::North);
+        fill_row(&mut matrix, 0);
+        set(&mut matrix, 1, 0, TetriminoType::T);
+        fill_row(&mut matrix, 2);
+        set(&mut matrix, 3, 5, TetriminoType::S);
+        fill_row(&mut matrix, 4);
+        set(&mut matrix, 5, 9, TetriminoType::Z);
+
+        assert_eq!(matrix.clear_lines(), 3);
+
+        let mut minos = matrix.get_minos();
+        minos.sort_by_key(|mino| (mino.row, mino.col));
+        assert_eq!(
+            minos,
+            vec![
+                Mino {
+                    col: 0,
+                    row: 0,
+                    tetrimino_type: TetriminoType::T,
+                },
+                Mino {
+                    col: 5,
+                    row: 1,
+                    tetrimino_type: TetriminoType::S,
+                },
+                Mino {
+                    col: 9,
+                    row: 2,
+                    tetrimino_type: TetriminoType::Z,
+                },
+            ]
+        );
+        assert_eq!(matrix.size(), (20, 10));
+    }
+
+    #[test]
+    fn clear_lines_leaves_incomplete_rows() {
+        let mut matrix = Matrix::new(4, MATRIX_WIDTH.into(), Facing::North);
+        for col in 0..MATRIX_WIDTH as i32 - 1 {
+            set(&mut matrix, 0, col, TetriminoType::O);
+        }
+        let before = matrix.clone();
+
+        assert_eq!(matrix.clear_lines(), 0);
+        assert_eq!(matrix, before);
+    }
+}
@@ -0,0 +1,425 @@
+use std::{io, path::PathBuf, time::Duration};
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    prelude::{Alignment, Buffer, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Clear, Paragraph, Widget},
+};
+
+use console_tetris::{
+    game::{GameOverReason, GameState},
+    rules::GameMode,
+};
+
+use crate::{
+    controls::{Action, Keymap},
+    high_scores::{self, HighScores},
+    settings::Settings,
+};
+
+/// Something that can be chosen from a [`Menu`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuAction {
+    Play,
+    /// Pick up the game saved when quitting
+    Continue,
+    /// Start a new game of the mode
+    Start(GameMode),
+    Resume,
+    Restart,
+    Settings,
+    /// Show the high score table
+    HighScores,
+    CycleHighScoreMode,
+    /// Put the result of the game on the high score table under the typed name
+    SaveHighScore,
+    MainMenu,
+    Quit,
+    ToggleGhost,
+    CycleDas,
+    CycleArr,
+    CycleSoftDrop,
+    CycleNextPreviews,
+    ToggleNextQueueSide,
+    CycleRandomizer,
+    CycleRotationSystem,
+    Controls,
+    CyclePreset,
+    /// Wait for a key to bind to the action
+    Rebind(Action),
+    Back,
+}
+
+/// A list of actions navigated with the arrow keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Menu {
+    title: String,
+    /// text shown above the items
+    body: Vec<String>,
+    items: Vec<(MenuAction, String)>,
+    /// the action taken when the menu is dismissed with Esc
+    cancel: MenuAction,
+    selected: usize,
+    /// whether the items are separated by blank lines
+    spaced: bool,
+}
+
+impl Menu {
+    pub fn new(title: &str, items: Vec<(MenuAction, String)>, cancel: MenuAction) -> Self {
+        Self {
+            title: title.to_owned(),
+            body: vec![],
+            items,
+            cancel,
+            selected: 0,
+            spaced: true,
+        }
+    }
+
+    /// The menu shown when the program starts
+    ///
+    /// `saved_game` adds the option to continue the game saved when quitting
+    pub fn main(saved_game: bool) -> Self {
+        let mut items = vec![
+            (MenuAction::Play, "Play".to_owned()),
+            (MenuAction::HighScores, "High Scores".to_owned()),
+            (MenuAction::Settings, "Settings".to_owned()),
+            (MenuAction::Quit, "Quit".to_owned()),
+        ];
+        if saved_game {
+            items.insert(0, (MenuAction::Continue, "Continue".to_owned()));
+        }
+
+        Self::new("TETRIS", items, MenuAction::Quit)
+    }
+
+    /// The game modes to choose from when starting a game
+    pub fn modes() -> Self {
+        let mut items: Vec<_> = GameMode::ALL
+            .into_iter()
+            .map(|mode| (MenuAction::Start(mode), mode.to_string()))
+            .collect();
+        items.push((MenuAction::Back, "Back".to_owned()));
+
+        Self::new("PLAY", items, MenuAction::Back)
+    }
+
+    /// The summary shown when a game ends
+    ///
+    /// `replay` is where the game's replay was saved
+    pub fn game_over(gamestate: &GameState, replay: Result<PathBuf, io::Error>) -> Self {
+        let scoring = &gamestate.scoring;
+        let replay = match replay {
+            Ok(path) => format!(
+                "Replay saved as {}",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            ),
+            Err(err) => format!("Could not save replay: {err}"),
+        };
+
+        let title = match gamestate.game_over {
+            Some(GameOverReason::Finished) => "FINISHED",
+            _ => "GAME OVER",
+        };
+
+        let mut body = vec![
+            gamestate
+                .game_over
+                .map_or(String::new(), |reason| reason.to_string()),
+            String::new(),
+            format!("Mode   {:>9}", gamestate.rules.mode),
+            format!("Score  {:>9}", scoring.score),
+            format!("Lines  {:>9}", scoring.lines),
+            format!("Level  {:>9}", scoring.level),
+            format!("Time   {:>9}", format_time(gamestate.play_time())),
+            format!("Pieces {:>9}", scoring.pieces),
+            format!("Seed   {:>9}", gamestate.seed),
+            replay,
+        ];
+        if !high_scores::ranked(gamestate) {
+            body.push("Custom rules, not ranked".to_owned());
+        }
+
+        Self::new(
+            title,
+            vec![
+                (MenuAction::Restart, "Restart".to_owned()),
+                (MenuAction::HighScores, "High Scores".to_owned()),
+                (MenuAction::MainMenu, "Main Menu".to_owned()),
+                (MenuAction::Quit, "Quit".to_owned()),
+            ],
+            MenuAction::MainMenu,
+        )
+        .with_body(body)
+    }
+
+    /// The prompt for the name to put a result on the high score table under
+    ///
+    /// `rank` is the index the result takes on the table of `mode`
+    pub fn new_high_score(mode: GameMode, rank: usize, name: &str) -> Self {
+        Self::new(
+            "NEW HIGH SCORE",
+            vec![(MenuAction::SaveHighScore, "Save".to_owned())],
+            MenuAction::Back,
+        )
+        .with_body(vec![
+            format!("#{} in {mode}", rank + 1),
+            String::new(),
+            format!("Name: {name}_"),
+            String::new(),
+            "Type a name, Enter saves, Esc skips".to_owned(),
+        ])
+    }
+
+    /// The high score table of `mode`
+    ///
+    /// `highlight` marks the result at that index, `error` is shown if saving failed
+    pub fn high_scores(
+        high_scores: &HighScores,
+        mode: GameMode,
+        highlight: Option<usize>,
+        error: Option<String>,
+    ) -> Self {
+        let entries = high_scores.get(mode);
+        let mut body = vec![format!(" {}", HighScores::header())];
+        body.extend(entries.iter().enumerate().map(|(index, entry)| {
+            let marker = if highlight == Some(index) { '>' } else { ' ' };
+            format!("{marker}{}", entry.row(index + 1))
+        }));
+        if entries.is_empty() {
+            body.push("No games yet".to_owned());
+        }
+        body.extend(error.map(|error| format!("Could not save: {error}")));
+
+        let mut menu = Self::new(
+            "HIGH SCORES",
+            vec![
+                (MenuAction::CycleHighScoreMode, format!("Mode: {mode}")),
+                (MenuAction::Back, "Back".to_owned()),
+            ],
+            MenuAction::Back,
+        )
+        .with_body(body);
+        menu.spaced = false;
+        menu
+    }
+
+    /// The menu shown while the game is paused
+    pub fn pause() -> Self {
+        Self::new(
+            "PAUSED",
+            vec![
+                (MenuAction::Resume, "Resume".to_owned()),
+                (MenuAction::Restart, "Restart".to_owned()),
+                (MenuAction::Settings, "Settings".to_owned()),
+                (MenuAction::Quit, "Quit".to_owned()),
+            ],
+            MenuAction::Resume,
+        )
+    }
+
+    /// The menu for changing [`Settings`]
+    ///
+    /// The randomizer and rotation system apply from the next game
+    pub fn settings(settings: &Settings) -> Self {
+        let on_off = |value: bool| if value { "On" } else { "Off" };
+        let or_mode = |choice: Option<String>| choice.unwrap_or_else(|| "Mode".to_owned());
+
+        Self::new(
+            "SETTINGS",
+            vec![
+                (
+                    MenuAction::ToggleGhost,
+                    format!("Ghost piece: {}", on_off(settings.ghost_piece)),
+                ),
+                (
+                    MenuAction::CycleDas,
+                    format!("DAS: {} ms", settings.das.as_millis()),
+                ),
+                (
+                    MenuAction::CycleArr,
+                    format!("ARR: {} ms", settings.arr.as_millis()),
+                ),
+                (
+                    MenuAction::CycleSoftDrop,
+                    format!("Soft drop: {}x", settings.soft_drop_factor),
+                ),
+                (
+                    MenuAction::CycleNextPreviews,
+                    format!("Next pieces: {}", settings.next_previews),
+                ),
+                (
+                    MenuAction::ToggleNextQueueSide,
+                    format!("Next queue: {}", settings.next_queue_side),
+                ),
+                (
+                    MenuAction::CycleRandomizer,
+                    format!(
+                        "Randomizer: {}",
+                        or_mode(settings.randomizer.map(|kind| kind.to_string()))
+                    ),
+                ),
+                (
+                    MenuAction::CycleRotationSystem,
+                    format!(
+                        "Rotation: {}",
+                        or_mode(settings.rotation_system.map(|kind| kind.to_string()))
+                    ),
+                ),
+                (MenuAction::Controls, "Controls".to_owned()),
+                (MenuAction::Back, "Back".to_owned()),
+            ],
+            MenuAction::Back,
+        )
+    }
+
+    /// The menu for rebinding the keys in `keymap`
+    ///
+    /// `rebinding` is the action waiting for a key, `error` is shown if saving failed
+    pub fn controls(keymap: &Keymap, rebinding: Option<Action>, error: Option<String>) -> Self {
+        let mut items = vec![(
+            MenuAction::CyclePreset,
+            format!("Preset: {}", keymap.preset),
+        )];
+        items.extend(Action::ALL.into_iter().map(|action| {
+            let keys = keymap
+                .keys(action)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            (MenuAction::Rebind(action), format!("{action}: {keys}"))
+        }));
+        items.push((MenuAction::Back, "Back".to_owned()));
+
+        let hint = match rebinding {
+            Some(action) => format!("Press a key for {action}, Backspace clears, Esc cancels"),
+            None => "Enter adds a key to an action".to_owned(),
+        };
+
+        let mut menu = Self::new("CONTROLS", items, MenuAction::Back).with_body(
+            [
+                Some(hint),
+                error.map(|error| format!("Could not save: {error}")),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+        );
+        menu.spaced = false;
+        menu
+    }
+
+    pub fn with_body(mut self, body: Vec<String>) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Select the item at `index`, keeping the selection in bounds
+    pub fn with_selected(mut self, index: usize) -> Self {
+        self.selected = index.min(self.items.len().saturating_sub(1));
+        self
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Move the selection in response to `key`
+    ///
+    /// Returns the chosen [`MenuAction`], if any
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<MenuAction> {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') | KeyCode::Char('w') => {
+                self.selected = self.selected.checked_sub(1).unwrap_or(self.items.len() - 1);
+                None
+            }
+            KeyCode::Down | KeyCode::Char('j') | KeyCode::Char('s') => {
+                self.selected = (self.selected + 1) % self.items.len();
+                None
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => Some(self.items[self.selected].0),
+            KeyCode::Esc => Some(self.cancel),
+            _ => None,
+        }
+    }
+}
+
+/// Format `time` as `minutes:seconds.hundredths`
+pub fn format_time(time: Duration) -> String {
+    let hundredths = time.as_millis() / 10;
+    format!(
+        "{}:{:02}.{:02}",
+        hundredths / 6000,
+        hundredths / 100 % 60,
+        hundredths % 100
+    )
+}
+
+/// Get a `width` by `height` [`Rect`] centered in `area`
+pub fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+impl Widget for &Menu {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let width = self
+            .items
+            .iter()
+            .map(|(_, label)| label.len())
+            .chain(self.body.iter().map(String::len))
+            .chain([self.title.len()])
+            .max()
+            .unwrap_or(0) as u16
+            + 8;
+
+        let mut lines: Vec<Line> = vec![];
+        if !self.body.is_empty() {
+            lines.push(Line::from(""));
+            lines.extend(self.body.iter().map(|line| Line::from(line.to_owned())));
+        }
+        if !self.spaced {
+            lines.push(Line::from(""));
+        }
+        lines.extend(
+            self.items
+                .iter()
+                .enumerate()
+                .flat_map(|(index, (_, label))| {
+                    let style = if index == self.selected {
+                        Style::default().add_modifier(Modifier::REVERSED)
+                    } else {
+                        Style::default()
+                    };
+                    let label = Line::styled(label.to_owned(), style);
+                    if self.spaced {
+                        vec![Line::from(""), label]
+                    } else {
+                        vec![label]
+                    }
+                }),
+        );
+
+        let area = centered_rect(width, lines.len() as u16 + 3, area);
+
+        Clear.render(area, buf);
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .title(self.title.to_owned())
+                    .borders(Borders::ALL),
+            )
+            .render(area, buf);
+    }
+}
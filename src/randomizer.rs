@@ -0,0 +1,311 @@
+use std::{collections::VecDeque, fmt};
+
+use rand::{seq::SliceRandom, thread_rng, Rng, RngCore};
+
+use crate::tetramino::TetriminoType;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A generator of the sequence of Tetriminos dealt to the player
+///
+/// The randomness comes from the `rng` passed to [`Randomizer::next`],
+/// a randomizer only keeps the state of its own algorithm
+pub trait Randomizer: fmt::Debug {
+    /// Pick the next Tetrimino
+    fn next(&mut self, rng: &mut dyn RngCore) -> TetriminoType;
+
+    /// Clone into a new box, so holders of a `Box<dyn Randomizer>` can be cloned
+    fn boxed_clone(&self) -> Box<dyn Randomizer>;
+}
+
+impl Clone for Box<dyn Randomizer> {
+    fn clone(&self) -> Self {
+        self.boxed_clone()
+    }
+}
+
+/// The largest seed a game can be played with
+///
+/// Saves are TOML, which only has signed 64 bit integers
+pub const MAX_SEED: u64 = i64::MAX as u64;
+
+/// Pick a seed for a game nobody asked a seed for
+///
+/// Kept to 9 digits so it is easy to read off the screen and share
+pub fn random_seed() -> u64 {
+    thread_rng().gen_range(0..1_000_000_000)
+}
+
+/// The available [`Randomizer`]s
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RandomizerKind {
+    #[default]
+    SevenBag,
+    FourteenBag,
+    PureRandom,
+    Nes,
+    Tgm,
+}
+
+impl RandomizerKind {
+    pub const ALL: [RandomizerKind; 5] = [
+        RandomizerKind::SevenBag,
+        RandomizerKind::FourteenBag,
+        RandomizerKind::PureRandom,
+        RandomizerKind::Nes,
+        RandomizerKind::Tgm,
+    ];
+
+    /// Create a fresh randomizer of this kind
+    pub fn build(self) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::SevenBag => Box::new(Bag::new(1)),
+            RandomizerKind::FourteenBag => Box::new(Bag::new(2)),
+            RandomizerKind::PureRandom => Box::new(PureRandom),
+            RandomizerKind::Nes => Box::new(Nes::default()),
+            RandomizerKind::Tgm => Box::new(History::default()),
+        }
+    }
+
+    /// The kind after this one, wrapping around
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|kind| *kind == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+impl fmt::Display for RandomizerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RandomizerKind::SevenBag => "7-bag",
+            RandomizerKind::FourteenBag => "14-bag",
+            RandomizerKind::PureRandom => "Random",
+            RandomizerKind::Nes => "NES",
+            RandomizerKind::Tgm => "TGM",
+        })
+    }
+}
+
+/// Deals shuffled bags holding `copies` of every Tetrimino
+///
+/// One copy is the guideline 7-bag, two copies a 14-bag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bag {
+    copies: usize,
+    bag: Vec<TetriminoType>,
+}
+
+impl Bag {
+    pub fn new(copies: usize) -> Self {
+        Self {
+            copies: copies.max(1),
+            bag: vec![],
+        }
+    }
+}
+
+impl Randomizer for Bag {
+    fn next(&mut self, rng: &mut dyn RngCore) -> TetriminoType {
+        // fill and shuffle if empty
+        if self.bag.is_empty() {
+            self.bag = TetriminoType::ALL.repeat(self.copies);
+            self.bag.shuffle(rng);
+        }
+
+        // bag is fed back to front but that order doesn't matter
+        self.bag.pop().expect("Bag was empty!")
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+/// Every Tetrimino is equally likely every time, with no memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PureRandom;
+
+impl Randomizer for PureRandom {
+    fn next(&mut self, rng: &mut dyn RngCore) -> TetriminoType {
+        rng.gen()
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Randomizer> {
+        Box::new(*self)
+    }
+}
+
+/// The NES randomizer
+///
+/// Rolls one of eight outcomes, if it is the previous Tetrimino or the
+/// eighth dummy outcome it rerolls once among the seven Tetriminos
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Nes {
+    last: Option<TetriminoType>,
+}
+
+impl Randomizer for Nes {
+    fn next(&mut self, rng: &mut dyn RngCore) -> TetriminoType {
+        let next = match TetriminoType::ALL.get(rng.gen_range(0..8)) {
+            Some(&roll) if Some(roll) != self.last => roll,
+            _ => rng.gen(),
+        };
+
+        self.last = Some(next);
+        next
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+/// The TGM randomizer
+///
+/// Rerolls a number of times while the roll is one of the last 4 Tetriminos.
+/// The history starts as Z, S, S, Z and the first Tetrimino is never an S, Z or O,
+/// so games never open with an overhang.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    history: VecDeque<TetriminoType>,
+    rolls: u32,
+    first: bool,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            history: VecDeque::from([
+                TetriminoType::Z,
+                TetriminoType::S,
+                TetriminoType::S,
+                TetriminoType::Z,
+            ]),
+            rolls: 6,
+            first: true,
+        }
+    }
+}
+
+impl Randomizer for History {
+    fn next(&mut self, rng: &mut dyn RngCore) -> TetriminoType {
+        let next = if self.first {
+            self.first = false;
+            *[
+                TetriminoType::I,
+                TetriminoType::J,
+                TetriminoType::L,
+                TetriminoType::T,
+            ]
+            .choose(rng)
+            .expect("No first Tetriminos")
+        } else {
+            let mut roll = rng.gen();
+            for _ in 1..self.rolls {
+                if !self.history.contains(&roll) {
+                    break;
+                }
+                roll = rng.gen();
+            }
+            roll
+        };
+
+        self.history.pop_front();
+        self.history.push_back(next);
+        next
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    /// Deal `count` Tetriminos from a fresh `kind` randomizer
+    fn deal(kind: RandomizerKind, seed: u64, count: usize) -> Vec<TetriminoType> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut randomizer = kind.build();
+        (0..count).map(|_| randomizer.next(&mut rng)).collect()
+    }
+
+    fn count(pieces: &[TetriminoType], tetrimino_type: TetriminoType) -> usize {
+        pieces
+            .iter()
+            .filter(|piece| **piece == tetrimino_type)
+            .count()
+    }
+
+    #[test]
+    fn seven_bag_deals_every_tetrimino_once_per_bag() {
+        for seed in 0..20 {
+            for bag in deal(RandomizerKind::SevenBag, seed, 7 * 10).chunks(7) {
+                for tetrimino_type in TetriminoType::ALL {
+                    assert_eq!(count(bag, tetrimino_type), 1, "{bag:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fourteen_bag_deals_every_tetrimino_twice_per_bag() {
+        for seed in 0..20 {
+            for bag in deal(RandomizerKind::FourteenBag, seed, 14 * 10).chunks(14) {
+                for tetrimino_type in TetriminoType::ALL {
+                    assert_eq!(count(bag, tetrimino_type), 2, "{bag:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tgm_never_opens_with_an_overhang() {
+        for seed in 0..100 {
+            let first = deal(RandomizerKind::Tgm, seed, 1)[0];
+            assert!(
+                !matches!(
+                    first,
+                    TetriminoType::S | TetriminoType::Z | TetriminoType::O
+                ),
+                "seed {seed} opened with {first:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn every_randomizer_deals_every_tetrimino() {
+        for kind in RandomizerKind::ALL {
+            let pieces = deal(kind, 7, 700);
+            for tetrimino_type in TetriminoType::ALL {
+                assert!(
+                    count(&pieces, tetrimino_type) > 0,
+                    "{kind} never dealt {tetrimino_type:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn the_largest_seed_fits_a_save() {
+        use crate::{
+            game::GameState,
+            rules::{GameMode, Rules},
+        };
+
+        for mode in GameMode::ALL {
+            let gamestate = GameState::new(Rules::from(mode), MAX_SEED);
+            let contents = toml::to_string(&gamestate).expect("game can be saved");
+            let loaded: GameState = toml::from_str(&contents).expect("save can be loaded");
+            assert_eq!(loaded, gamestate);
+        }
+
+        let too_large = GameState::new(Rules::default(), MAX_SEED + 1);
+        assert!(toml::to_string(&too_large).is_err());
+    }
+}
@@ -0,0 +1,376 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{GameState, Movement},
+    randomizer::RandomizerKind,
+    rotation::{RotationDirection, RotationSystemKind},
+    rules::{GameMode, Rules},
+};
+
+/// The first line of every replay
+const HEADER: &str = "console_tetris replay 1";
+
+/// Something the player did to a game, applied with [`GameState::input`]
+///
+/// Written the same way as in a [`Replay`], like `cw` or `soft 20`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub enum Input {
+    Move(Movement),
+    Hold,
+    NewTetrimino,
+    /// Start soft dropping at the factor, or stop with [`None`]
+    SoftDrop(Option<u32>),
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Move(Movement::Left) => f.write_str("left"),
+            Input::Move(Movement::Right) => f.write_str("right"),
+            Input::Move(Movement::Down) => f.write_str("down"),
+            Input::Move(Movement::Drop) => f.write_str("drop"),
+            Input::Move(Movement::Rotate(RotationDirection::Clockwise)) => f.write_str("cw"),
+            Input::Move(Movement::Rotate(RotationDirection::Counterclockwise)) => {
+                f.write_str("ccw")
+            }
+            Input::Move(Movement::Rotate(RotationDirection::Half)) => f.write_str("180"),
+            Input::Hold => f.write_str("hold"),
+            Input::NewTetrimino => f.write_str("new"),
+            Input::SoftDrop(Some(factor)) => write!(f, "soft {factor}"),
+            Input::SoftDrop(None) => f.write_str("soft off"),
+        }
+    }
+}
+
+impl FromStr for Input {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "left" => Input::Move(Movement::Left),
+            "right" => Input::Move(Movement::Right),
+            "down" => Input::Move(Movement::Down),
+            "drop" => Input::Move(Movement::Drop),
+            "cw" => Input::Move(Movement::Rotate(RotationDirection::Clockwise)),
+            "ccw" => Input::Move(Movement::Rotate(RotationDirection::Counterclockwise)),
+            "180" => Input::Move(Movement::Rotate(RotationDirection::Half)),
+            "hold" => Input::Hold,
+            "new" => Input::NewTetrimino,
+            "soft off" => Input::SoftDrop(None),
+            _ => {
+                let factor = s
+                    .strip_prefix("soft ")
+                    .and_then(|factor| factor.parse().ok())
+                    .ok_or_else(|| format!("unknown input `{s}`"))?;
+                Input::SoftDrop(Some(factor))
+            }
+        })
+    }
+}
+
+impl TryFrom<String> for Input {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Input> for String {
+    fn from(value: Input) -> Self {
+        value.to_string()
+    }
+}
+
+/// Everything needed to play a game again exactly as it went
+///
+/// Written as a header, the seed and rules one per line, then one
+/// `frame input` line for every [`Input`]:
+///
+/// ```text
+/// console_tetris replay 1
+/// seed 1234
+/// frames 3600
+/// mode Marathon
+/// randomizer 7-bag
+/// rotation SRS
+/// lock_delay 500
+/// lock_resets 15
+/// partial_lock_out false
+/// 0 soft 20
+/// 14 soft off
+/// 20 drop
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Replay {
+    pub seed: u64,
+    pub rules: Rules,
+    /// How many frames the game lasted
+    pub frames: u64,
+    /// The inputs in the order they were applied, with the frame each was applied on
+    pub inputs: Vec<(u64, Input)>,
+}
+
+impl From<&GameState> for Replay {
+    fn from(gamestate: &GameState) -> Self {
+        Self {
+            seed: gamestate.seed,
+            rules: gamestate.rules.to_owned(),
+            frames: gamestate.frame,
+            inputs: gamestate.inputs.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rules = &self.rules;
+        writeln!(f, "{HEADER}")?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "frames {}", self.frames)?;
+        writeln!(f, "mode {}", rules.mode)?;
+        writeln!(f, "randomizer {}", rules.randomizer)?;
+        writeln!(f, "rotation {}", rules.rotation_system)?;
+        writeln!(f, "lock_delay {}", rules.lock_delay.as_millis())?;
+        writeln!(f, "lock_resets {}", rules.max_lock_resets)?;
+        writeln!(f, "partial_lock_out {}", rules.partial_lock_out)?;
+        for (frame, input) in &self.inputs {
+            writeln!(f, "{frame} {input}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Replay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().enumerate();
+        if lines.next().map(|(_, line)| line) != Some(HEADER) {
+            return Err("not a replay".to_owned());
+        }
+
+        let mut replay = Self::default();
+        for (number, line) in lines {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let invalid = || format!("line {}: invalid {key} `{value}`", number + 1);
+
+            let rules = &mut replay.rules;
+            match key {
+                "seed" => replay.seed = value.parse().map_err(|_| invalid())?,
+                "frames" => replay.frames = value.parse().map_err(|_| invalid())?,
+                "mode" => rules.mode = parse_named(&GameMode::ALL, value).ok_or_else(invalid)?,
+                "randomizer" => {
+                    rules.randomizer =
+                        parse_named(&RandomizerKind::ALL, value).ok_or_else(invalid)?
+                }
+                "rotation" => {
+                    rules.rotation_system =
+                        parse_named(&RotationSystemKind::ALL, value).ok_or_else(invalid)?
+                }
+                "lock_delay" => {
+                    rules.lock_delay = Duration::from_millis(value.parse().map_err(|_| invalid())?)
+                }
+                "lock_resets" => rules.max_lock_resets = value.parse().map_err(|_| invalid())?,
+                "partial_lock_out" => {
+                    rules.partial_lock_out = value.parse().map_err(|_| invalid())?
+                }
+                "" => (),
+                frame => {
+                    let frame = frame
+                        .parse()
+                        .map_err(|_| format!("line {}: unknown key `{key}`", number + 1))?;
+                    let input = value
+                        .parse()
+                        .map_err(|err| format!("line {}: {err}", number + 1))?;
+                    replay.inputs.push((frame, input));
+                }
+            }
+        }
+
+        Ok(replay)
+    }
+}
+
+/// The one of `choices` displayed as `name`
+fn parse_named<T: fmt::Display + Copy>(choices: &[T], name: &str) -> Option<T> {
+    choices
+        .iter()
+        .copied()
+        .find(|choice| choice.to_string() == name)
+}
+
+/// A [`Replay`] being played back through a [`GameState`]
+///
+/// The game shows the state after the inputs of [`Playback::frame`] were applied
+#[derive(Debug, Clone)]
+pub struct Playback {
+    replay: Replay,
+    pub gamestate: GameState,
+    /// The index of the first input not applied yet
+    next_input: usize,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        let gamestate = GameState::new(replay.rules.to_owned(), replay.seed);
+        let mut playback = Self {
+            replay,
+            gamestate,
+            next_input: 0,
+        };
+        playback.apply_inputs();
+        playback
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// The frame the game is on
+    pub fn frame(&self) -> u64 {
+        self.gamestate.frame
+    }
+
+    /// Whether the game has been played to its end
+    pub fn finished(&self) -> bool {
+        self.gamestate.game_over.is_some() || self.frame() >= self.replay.frames
+    }
+
+    /// Advance one frame and apply the inputs made on it
+    ///
+    /// Returns `false` once the replay is finished
+    pub fn step(&mut self) -> bool {
+        if self.finished() {
+            return false;
+        }
+
+        self.gamestate.step();
+        self.apply_inputs();
+        true
+    }
+
+    /// Jump to `frame`, or the end if the replay is shorter
+    ///
+    /// Going back plays the game again from the start
+    pub fn seek(&mut self, frame: u64) {
+        if frame < self.frame() {
+            *self = Self::new(std::mem::take(&mut self.replay));
+        }
+
+        while self.frame() < frame && self.step() {}
+    }
+
+    fn apply_inputs(&mut self) {
+        while let Some((frame, input)) = self.replay.inputs.get(self.next_input) {
+            if *frame > self.gamestate.frame {
+                break;
+            }
+
+            self.gamestate.input(*input);
+            self.next_input += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    /// Play a game of `mode` with random inputs for up to `frames` frames
+    fn play(mode: GameMode, seed: u64, frames: u64) -> GameState {
+        let inputs = [
+            Input::Move(Movement::Left),
+            Input::Move(Movement::Right),
+            Input::Move(Movement::Down),
+            Input::Move(Movement::Drop),
+            Input::Move(Movement::Rotate(RotationDirection::Clockwise)),
+            Input::Move(Movement::Rotate(RotationDirection::Counterclockwise)),
+            Input::Move(Movement::Rotate(RotationDirection::Half)),
+            Input::Hold,
+            Input::SoftDrop(Some(20)),
+            Input::SoftDrop(None),
+        ];
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut gamestate = GameState::new(Rules::from(mode), seed);
+
+        while gamestate.frame < frames {
+            // a pause in the middle of the game must not throw the replay off
+            if gamestate.frame == 100 {
+                gamestate.pause();
+                gamestate.input(Input::Hold);
+                gamestate.resume();
+            }
+            if rng.gen_ratio(1, 6) {
+                gamestate.input(inputs[rng.gen_range(0..inputs.len())]);
+            }
+            if !gamestate.step() {
+                break;
+            }
+        }
+        gamestate
+    }
+
+    #[test]
+    fn replay_text_round_trip() {
+        for mode in GameMode::ALL {
+            let replay = Replay::from(&play(mode, 42, 3_000));
+            assert!(!replay.inputs.is_empty());
+            assert_eq!(replay.to_string().parse::<Replay>(), Ok(replay));
+        }
+    }
+
+    #[test]
+    fn replay_rejects_unknown_lines() {
+        assert!("seed 1\n".parse::<Replay>().is_err());
+        assert!(format!("{HEADER}\nseed x\n").parse::<Replay>().is_err());
+        assert!(format!("{HEADER}\n12 jump\n").parse::<Replay>().is_err());
+    }
+
+    #[test]
+    fn playback_ends_in_the_recorded_game() {
+        for mode in GameMode::ALL {
+            for seed in 0..3 {
+                let gamestate = play(mode, seed, 20_000);
+                let mut playback = Playback::new(Replay::from(&gamestate));
+                while playback.step() {}
+
+                assert_eq!(playback.gamestate.scoring, gamestate.scoring);
+                assert_eq!(playback.gamestate, gamestate, "{mode} with seed {seed}");
+            }
+        }
+    }
+
+    #[test]
+    fn seeking_back_replays_from_the_start() {
+        let gamestate = play(GameMode::Marathon, 7, 20_000);
+        let middle = gamestate.frame / 2;
+        let mut playback = Playback::new(Replay::from(&gamestate));
+
+        playback.seek(middle);
+        assert_eq!(playback.frame(), middle);
+        let at_middle = playback.gamestate.clone();
+
+        playback.seek(u64::MAX);
+        assert!(playback.finished());
+        assert_eq!(playback.gamestate, gamestate);
+
+        playback.seek(middle);
+        assert_eq!(playback.gamestate, at_middle);
+
+        playback.seek(0);
+        assert_eq!(playback.frame(), 0);
+        assert_eq!(playback.gamestate.scoring.pieces, 0);
+    }
+}
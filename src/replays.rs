@@ -0,0 +1,196 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crossterm::event::{KeyCode, KeyEvent};
+
+use console_tetris::{
+    clock,
+    replay::{Playback, Replay},
+};
+
+use crate::menu::format_time;
+
+/// The playback speeds to choose from, in quarters of real time
+const SPEEDS: [u64; 5] = [1, 2, 4, 8, 16];
+/// The index of real time in [`SPEEDS`]
+const REAL_TIME: usize = 2;
+/// How far the arrow keys seek
+const SEEK_STEP: Duration = Duration::from_secs(5);
+
+/// The directory finished games are saved in
+pub fn dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("console_tetris").join("replays"))
+}
+
+/// Save `replay` in `dir` under the time it was saved at and its seed
+///
+/// Never replaces an earlier replay, a number is added to the name if it is taken.
+/// Returns the path it was saved to
+pub fn save(dir: &Path, replay: &Replay) -> Result<PathBuf, io::Error> {
+    fs::create_dir_all(dir)?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let name = format!("{}-{}", now.as_millis(), replay.seed);
+
+    let mut attempt = 0;
+    loop {
+        let path = match attempt {
+            0 => dir.join(format!("{name}.replay")),
+            _ => dir.join(format!("{name}-{attempt}.replay")),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(replay.to_string().as_bytes())?;
+                return Ok(path);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Load the replay at `path`
+pub fn load(path: &Path) -> Result<Replay, io::Error> {
+    fs::read_to_string(path)?.parse().map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {err}", path.display()),
+        )
+    })
+}
+
+/// Watches a [`Replay`] with pause, frame stepping, seeking and speed controls
+#[derive(Debug, Clone)]
+pub struct ReplayViewer {
+    pub playback: Playback,
+    pub paused: bool,
+    /// The index of the playback speed in [`SPEEDS`]
+    speed: usize,
+    /// Quarter frames played since the last whole frame
+    quarters: u64,
+}
+
+impl ReplayViewer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            playback: Playback::new(replay),
+            paused: false,
+            speed: REAL_TIME,
+            quarters: 0,
+        }
+    }
+
+    /// Apply a key press
+    ///
+    /// Space plays and pauses, `,` and `.` step one frame, the left and right
+    /// arrows seek, the up and down arrows change the speed and Home restarts
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        let frame = self.playback.frame();
+        let seek_step = clock::frames(SEEK_STEP);
+
+        match key.code {
+            KeyCode::Char(' ') | KeyCode::Char('p') => {
+                if self.paused && self.playback.finished() {
+                    self.playback.seek(0);
+                }
+                self.paused = !self.paused;
+            }
+            KeyCode::Char('.') => {
+                self.paused = true;
+                self.playback.step();
+            }
+            KeyCode::Char(',') => {
+                self.paused = true;
+                self.playback.seek(frame.saturating_sub(1));
+            }
+            KeyCode::Left => self.playback.seek(frame.saturating_sub(seek_step)),
+            KeyCode::Right => self.playback.seek(frame + seek_step),
+            KeyCode::Home => self.playback.seek(0),
+            KeyCode::End => self.playback.seek(u64::MAX),
+            KeyCode::Up | KeyCode::Char('+') => self.speed = (self.speed + 1).min(SPEEDS.len() - 1),
+            KeyCode::Down | KeyCode::Char('-') => self.speed = self.speed.saturating_sub(1),
+            _ => (),
+        }
+    }
+
+    /// Play the game frames that fit in `frames` real frames at the current speed
+    ///
+    /// Pauses at the end of the replay
+    pub fn advance(&mut self, frames: u64) {
+        if self.paused {
+            return;
+        }
+
+        self.quarters += frames * SPEEDS[self.speed];
+        for _ in 0..self.quarters / 4 {
+            if !self.playback.step() {
+                self.paused = true;
+                break;
+            }
+        }
+        self.quarters %= 4;
+    }
+
+    /// The number of real frames until [`ReplayViewer::advance`] has work to do
+    pub fn next_deadline(&self) -> Option<u64> {
+        if self.paused {
+            return None;
+        }
+
+        Some((4 - self.quarters).div_ceil(SPEEDS[self.speed]))
+    }
+
+    /// A line describing where the replay is at
+    pub fn status(&self) -> String {
+        let state = match (self.paused, self.playback.finished()) {
+            (_, true) => "Ended",
+            (true, false) => "Paused",
+            (false, false) => "Playing",
+        };
+        let speed = SPEEDS[self.speed] as f64 / 4.0;
+
+        format!(
+            "REPLAY {state} {speed}x {} / {}",
+            format_time(clock::duration(self.playback.frame())),
+            format_time(clock::duration(self.playback.replay().frames)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use console_tetris::{game::GameState, rules::Rules};
+
+    use super::*;
+
+    #[test]
+    fn replays_saved_together_keep_their_own_files() {
+        let dir = env::temp_dir().join(format!("console_tetris-replays-{}", process::id()));
+        let first = Replay::from(&GameState::new(Rules::default(), 7));
+        let mut second = first.to_owned();
+        second.frames += 1;
+
+        // the same seed saved many times within the same second
+        let paths: Vec<PathBuf> = [&first, &second, &first]
+            .into_iter()
+            .map(|replay| save(&dir, replay).expect("replay can be saved"))
+            .collect();
+        assert_ne!(paths[0], paths[1]);
+        assert_ne!(paths[1], paths[2]);
+        assert_ne!(paths[0], paths[2]);
+
+        assert_eq!(load(&paths[0]).expect("replay can be loaded"), first);
+        assert_eq!(load(&paths[1]).expect("replay can be loaded"), second);
+        assert_eq!(load(&paths[2]).expect("replay can be loaded"), first);
+
+        fs::remove_dir_all(dir).expect("temporary directory can be removed");
+    }
+}
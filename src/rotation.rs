@@ -0,0 +1,423 @@
+use std::fmt;
+
+use grid::Grid;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    matrix::{GridRotation, Matrix},
+    tetramino::{Facing, Tetrimino, TetriminoType},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RotationDirection {
+    Clockwise,
+    Counterclockwise,
+    /// Half a turn, 180°
+    Half,
+}
+
+/// The rules for how Tetriminos look and turn
+///
+/// Shapes are drawn in a 5x5 bound-box, row 0 at the top
+pub trait RotationSystem: fmt::Debug + Sync {
+    /// The minos of `tetrimino_type` facing `facing`
+    fn shape(&self, tetrimino_type: TetriminoType, facing: Facing) -> Matrix;
+
+    /// The facing Tetriminos spawn with
+    fn spawn_facing(&self, _tetrimino_type: TetriminoType) -> Facing {
+        Facing::North
+    }
+
+    /// The offsets to try in order when rotating from `from` into `rotated`
+    ///
+    /// `rotated` is already in its new shape at the position it was rotated in.
+    /// The first offset that fits is used, no offsets means the rotation is blocked.
+    fn kicks(&self, rotated: &Tetrimino, from: Facing, matrix: &Matrix) -> Vec<(i32, i32)>;
+}
+
+/// The available [`RotationSystem`]s
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RotationSystemKind {
+    #[default]
+    Srs,
+    Ars,
+    Nrs,
+    NoKicks,
+}
+
+impl RotationSystemKind {
+    pub const ALL: [RotationSystemKind; 4] = [
+        RotationSystemKind::Srs,
+        RotationSystemKind::Ars,
+        RotationSystemKind::Nrs,
+        RotationSystemKind::NoKicks,
+    ];
+
+    pub fn system(self) -> &'static dyn RotationSystem {
+        match self {
+            RotationSystemKind::Srs => &Srs,
+            RotationSystemKind::Ars => &Ars,
+            RotationSystemKind::Nrs => &Nrs,
+            RotationSystemKind::NoKicks => &NoKicks,
+        }
+    }
+
+    /// The kind after this one, wrapping around
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|kind| *kind == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+impl fmt::Display for RotationSystemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RotationSystemKind::Srs => "SRS",
+            RotationSystemKind::Ars => "ARS",
+            RotationSystemKind::Nrs => "NRS",
+            RotationSystemKind::NoKicks => "No kicks",
+        })
+    }
+}
+
+/// Build a shape from a drawing, `#` marks a mino
+fn shape(rows: [&str; 5], tetrimino_type: TetriminoType, facing: Facing) -> Matrix {
+    let cells = rows
+        .iter()
+        .flat_map(|row| row.chars().map(|c| (c == '#').then_some(tetrimino_type)))
+        .collect();
+
+    let mut matrix = Matrix::from(Grid::from_vec(cells, 5));
+    matrix.rotation = facing;
+    matrix
+}
+
+/// Turn `spawn` clockwise about the center of the bound-box until it faces `facing`
+fn turned(spawn: Matrix, facing: Facing) -> Matrix {
+    (0..facing as usize).fold(spawn, |shape, _| {
+        shape.rotated(RotationDirection::Clockwise)
+    })
+}
+
+/// The guideline Super Rotation System
+///
+/// Tetriminos turn about a center, then try the offsets from
+/// [`TetriminoType::get_offset_data`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Srs;
+
+impl Srs {
+    fn spawn_shape(tetrimino_type: TetriminoType) -> Matrix {
+        let rows = match tetrimino_type {
+            TetriminoType::O => [".....", "..##.", "..##.", ".....", "....."],
+            TetriminoType::I => [".....", ".....", ".####", ".....", "....."],
+            TetriminoType::T => [".....", "..#..", ".###.", ".....", "....."],
+            TetriminoType::L => [".....", "...#.", ".###.", ".....", "....."],
+            TetriminoType::J => [".....", ".#...", ".###.", ".....", "....."],
+            TetriminoType::S => [".....", "..##.", ".##..", ".....", "....."],
+            TetriminoType::Z => [".....", ".##..", "..##.", ".....", "....."],
+        };
+        shape(rows, tetrimino_type, Facing::North)
+    }
+}
+
+impl RotationSystem for Srs {
+    fn shape(&self, tetrimino_type: TetriminoType, facing: Facing) -> Matrix {
+        turned(Self::spawn_shape(tetrimino_type), facing)
+    }
+
+    fn kicks(&self, rotated: &Tetrimino, from: Facing, _matrix: &Matrix) -> Vec<(i32, i32)> {
+        rotated
+            .tetrimino_type()
+            .get_offset_data(from, rotated.facing())
+    }
+}
+
+/// SRS shapes that only turn in place
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoKicks;
+
+impl RotationSystem for NoKicks {
+    fn shape(&self, tetrimino_type: TetriminoType, facing: Facing) -> Matrix {
+        Srs.shape(tetrimino_type, facing)
+    }
+
+    fn kicks(&self, _rotated: &Tetrimino, _from: Facing, _matrix: &Matrix) -> Vec<(i32, i32)> {
+        vec![(0, 0)]
+    }
+}
+
+/// The classic Nintendo Rotation System
+///
+/// Tetriminos spawn flat side up and never kick. S, Z and I only have two
+/// states, standing on the right of center, and O doesn't turn at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Nrs;
+
+impl RotationSystem for Nrs {
+    fn shape(&self, tetrimino_type: TetriminoType, facing: Facing) -> Matrix {
+        let flat = matches!(facing, Facing::North | Facing::South);
+        let rows = match (tetrimino_type, flat) {
+            (TetriminoType::O, _) => [".....", ".....", ".##..", ".##..", "....."],
+            (TetriminoType::I, true) => [".....", ".....", "####.", ".....", "....."],
+            (TetriminoType::I, false) => ["..#..", "..#..", "..#..", "..#..", "....."],
+            (TetriminoType::S, true) => [".....", ".....", "..##.", ".##..", "....."],
+            (TetriminoType::S, false) => [".....", "..#..", "..##.", "...#.", "....."],
+            (TetriminoType::Z, true) => [".....", ".....", ".##..", "..##.", "....."],
+            (TetriminoType::Z, false) => [".....", "...#.", "..##.", "..#..", "....."],
+            (TetriminoType::T, _) => {
+                let spawn = [".....", ".....", ".###.", "..#..", "....."];
+                return turned(shape(spawn, tetrimino_type, Facing::North), facing);
+            }
+            (TetriminoType::L, _) => {
+                let spawn = [".....", ".....", ".###.", ".#...", "....."];
+                return turned(shape(spawn, tetrimino_type, Facing::North), facing);
+            }
+            (TetriminoType::J, _) => {
+                let spawn = [".....", ".....", ".###.", "...#.", "....."];
+                return turned(shape(spawn, tetrimino_type, Facing::North), facing);
+            }
+        };
+        shape(rows, tetrimino_type, facing)
+    }
+
+    fn kicks(&self, _rotated: &Tetrimino, _from: Facing, _matrix: &Matrix) -> Vec<(i32, i32)> {
+        vec![(0, 0)]
+    }
+}
+
+/// The Arika Rotation System from the TGM series
+///
+/// Tetriminos spawn flat side up and rest on the bottom of their box in every
+/// state. A blocked rotation tries one column right, then one column left,
+/// except for I and when the first blocked cell of an L, J or T is in the
+/// center column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ars;
+
+impl RotationSystem for Ars {
+    fn shape(&self, tetrimino_type: TetriminoType, facing: Facing) -> Matrix {
+        let rows = match (tetrimino_type, facing) {
+            (TetriminoType::O, _) => [".....", ".....", "..##.", "..##.", "....."],
+            (TetriminoType::I, Facing::North | Facing::South) => {
+                [".....", ".....", ".####", ".....", "....."]
+            }
+            (TetriminoType::I, _) => [".....", "...#.", "...#.", "...#.", "...#."],
+            (TetriminoType::S, Facing::North | Facing::South) => {
+                [".....", ".....", "..##.", ".##..", "....."]
+            }
+            (TetriminoType::S, _) => [".....", ".#...", ".##..", "..#..", "....."],
+            (TetriminoType::Z, Facing::North | Facing::South) => {
+                [".....", ".....", ".##..", "..##.", "....."]
+            }
+            (TetriminoType::Z, _) => [".....", "...#.", "..##.", "..#..", "....."],
+            (TetriminoType::T, Facing::North) => [".....", ".....", ".###.", "..#..", "....."],
+            (TetriminoType::T, Facing::East) => [".....", "..#..", ".##..", "..#..", "....."],
+            (TetriminoType::T, Facing::South) => [".....", ".....", "..#..", ".###.", "....."],
+            (TetriminoType::T, Facing::West) => [".....", "..#..", "..##.", "..#..", "....."],
+            (TetriminoType::L, Facing::North) => [".....", ".....", ".###.", ".#...", "....."],
+            (TetriminoType::L, Facing::East) => [".....", ".##..", "..#..", "..#..", "....."],
+            (TetriminoType::L, Facing::South) => [".....", ".....", "...#.", ".###.", "....."],
+            (TetriminoType::L, Facing::West) => [".....", "..#..", "..#..", "..##.", "....."],
+            (TetriminoType::J, Facing::North) => [".....", ".....", ".###.", "...#.", "....."],
+            (TetriminoType::J, Facing::East) => [".....", "..#..", "..#..", ".##..", "....."],
+            (TetriminoType::J, Facing::South) => [".....", ".....", ".#...", ".###.", "....."],
+            (TetriminoType::J, Facing::West) => [".....", "..##.", "..#..", "..#..", "....."],
+        };
+        shape(rows, tetrimino_type, facing)
+    }
+
+    fn kicks(&self, rotated: &Tetrimino, _from: Facing, matrix: &Matrix) -> Vec<(i32, i32)> {
+        let tetrimino_type = rotated.tetrimino_type();
+        if tetrimino_type == TetriminoType::I {
+            return vec![(0, 0)];
+        }
+
+        if matches!(
+            tetrimino_type,
+            TetriminoType::L | TetriminoType::J | TetriminoType::T
+        ) {
+            // the blocked cells in reading order, top to bottom then left to right
+            let (col, _) = rotated.position();
+            let mut blocked = rotated.position_invalid(0, 0, matrix).unwrap_or_default();
+            blocked.sort_by_key(|mino| (-mino.row, mino.col));
+
+            if blocked.first().is_some_and(|mino| mino.col == col + 2) {
+                return vec![(0, 0)];
+            }
+        }
+
+        vec![(0, 0), (1, 0), (-1, 0)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        matrix::{MinoGrid, MATRIX_BUFFER_HEIGHT, MATRIX_HEIGHT, MATRIX_WIDTH},
+        tetramino::Mino,
+    };
+
+    /// An empty matrix with `(col, row)` filled
+    fn matrix_with(filled: &[(i32, i32)]) -> Matrix {
+        let mut matrix = Matrix::new(
+            (MATRIX_HEIGHT + MATRIX_BUFFER_HEIGHT).into(),
+            MATRIX_WIDTH.into(),
+            Facing::North,
+        );
+        for &(col, row) in filled {
+            matrix.set_mino(Mino {
+                col,
+                row,
+                tetrimino_type: TetriminoType::O,
+            });
+        }
+        matrix
+    }
+
+    /// A `tetrimino_type` of `kind` in its spawn state, moved down into the middle of
+    /// the matrix with the top-left corner of its bound-box at `(2, 10)`
+    fn in_the_middle(kind: RotationSystemKind, tetrimino_type: TetriminoType) -> Tetrimino {
+        let mut tetrimino = Tetrimino::new(tetrimino_type, kind);
+        let (col, row) = tetrimino.position();
+        assert!(tetrimino.move_position(2 - col, 10 - row, &matrix_with(&[])));
+        tetrimino
+    }
+
+    /// The sorted `(col, row)` of every mino of `tetrimino`
+    fn cells(tetrimino: &Tetrimino) -> Vec<(i32, i32)> {
+        let mut cells: Vec<(i32, i32)> = tetrimino
+            .get_minos()
+            .iter()
+            .map(|mino| (mino.col, mino.row))
+            .collect();
+        cells.sort();
+        cells
+    }
+
+    /// `tetrimino` turned in `direction` in an empty matrix
+    fn turned_freely(tetrimino: &Tetrimino, direction: RotationDirection) -> Tetrimino {
+        let mut turned = tetrimino.to_owned();
+        assert!(turned.rotate(direction, &matrix_with(&[])));
+        turned
+    }
+
+    #[test]
+    fn no_kicks_fails_every_blocked_rotation() {
+        let directions = [
+            RotationDirection::Clockwise,
+            RotationDirection::Counterclockwise,
+            RotationDirection::Half,
+        ];
+        for tetrimino_type in TetriminoType::ALL {
+            let tetrimino = in_the_middle(RotationSystemKind::NoKicks, tetrimino_type);
+            let before = cells(&tetrimino);
+
+            for direction in directions {
+                // block one cell the turned Tetrimino needs, every other offset is free
+                let Some(&blocked) = cells(&turned_freely(&tetrimino, direction))
+                    .iter()
+                    .find(|cell| !before.contains(cell))
+                else {
+                    continue;
+                };
+                let matrix = matrix_with(&[blocked]);
+
+                let mut turned = tetrimino.to_owned();
+                assert!(
+                    !turned.rotate(direction, &matrix),
+                    "{tetrimino_type:?} {direction:?}"
+                );
+                assert_eq!(turned, tetrimino);
+            }
+        }
+    }
+
+    #[test]
+    fn nrs_i_s_and_z_toggle_right_of_center() {
+        for tetrimino_type in [TetriminoType::I, TetriminoType::S, TetriminoType::Z] {
+            let flat = in_the_middle(RotationSystemKind::Nrs, tetrimino_type);
+            let standing = turned_freely(&flat, RotationDirection::Clockwise);
+
+            // both directions give the same standing state, and turning again lays it flat
+            assert_eq!(
+                cells(&turned_freely(&flat, RotationDirection::Counterclockwise)),
+                cells(&standing),
+                "{tetrimino_type:?}"
+            );
+            for direction in [
+                RotationDirection::Clockwise,
+                RotationDirection::Counterclockwise,
+            ] {
+                assert_eq!(
+                    cells(&turned_freely(&standing, direction)),
+                    cells(&flat),
+                    "{tetrimino_type:?} {direction:?}"
+                );
+            }
+
+            // standing in the center column and the one to its right
+            let (col, _) = flat.position();
+            let center = col + 2;
+            let cols: Vec<i32> = cells(&standing).iter().map(|(col, _)| *col).collect();
+            assert!(cols.contains(&center), "{tetrimino_type:?} {cols:?}");
+            assert!(
+                cols.iter().all(|col| (center..=center + 1).contains(col)),
+                "{tetrimino_type:?} {cols:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn ars_refuses_to_kick_off_the_center_column() {
+        // an L turning clockwise needs the top two cells of its box and the bottom
+        // of the center column, with the corner of the box at (2, 10)
+        let l = in_the_middle(RotationSystemKind::Ars, TetriminoType::L);
+
+        // blocked first in the center column, no kick
+        let mut turned = l.to_owned();
+        assert!(!turned.rotate(RotationDirection::Clockwise, &matrix_with(&[(4, 7)])));
+        assert_eq!(turned, l);
+
+        // blocked first on the left, kicked one column right
+        let mut turned = l.to_owned();
+        assert!(turned.rotate(RotationDirection::Clockwise, &matrix_with(&[(3, 9)])));
+        assert_eq!(turned.facing(), Facing::East);
+        assert_eq!(turned.position(), (3, 10));
+
+        // a T turning onto a blocked center column doesn't kick either
+        let t = in_the_middle(RotationSystemKind::Ars, TetriminoType::T);
+        let mut turned = t.to_owned();
+        assert!(!turned.rotate(RotationDirection::Clockwise, &matrix_with(&[(4, 9)])));
+        assert_eq!(turned, t);
+    }
+
+    #[test]
+    fn spawn_shapes_fit_the_spawn_point() {
+        let matrix = matrix_with(&[]);
+        for kind in [RotationSystemKind::Ars, RotationSystemKind::Nrs] {
+            for tetrimino_type in TetriminoType::ALL {
+                let tetrimino = Tetrimino::new(tetrimino_type, kind);
+                assert_eq!(
+                    tetrimino.position_invalid(0, 0, &matrix),
+                    None,
+                    "{kind} {tetrimino_type:?}"
+                );
+
+                // inside the spawn bound-box, across the top of the visible matrix
+                let (left, _) = tetrimino.position();
+                for (col, row) in cells(&tetrimino) {
+                    assert!((left..left + 5).contains(&col), "{kind} {tetrimino_type:?}");
+                    assert!(
+                        (i32::from(MATRIX_HEIGHT) - 1..=i32::from(MATRIX_HEIGHT)).contains(&row),
+                        "{kind} {tetrimino_type:?}"
+                    );
+                }
+            }
+        }
+    }
+}
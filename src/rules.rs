@@ -0,0 +1,130 @@
+use std::{fmt, time::Duration};
+
+use crate::{randomizer::RandomizerKind, rotation::RotationSystemKind};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A way to play, each with its own [`Rules`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum GameMode {
+    /// Guideline play with SRS and a 7-bag
+    #[default]
+    Marathon,
+    /// Guideline play until 40 lines are cleared, as fast as possible
+    Sprint,
+    /// NES style play with NRS, no kicks, no lock resets and nothing locked above
+    /// the visible matrix
+    Classic,
+    /// TGM style play with ARS and the TGM randomizer
+    Arcade,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 4] = [
+        GameMode::Marathon,
+        GameMode::Sprint,
+        GameMode::Classic,
+        GameMode::Arcade,
+    ];
+
+    /// The mode after this one, wrapping around
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|mode| *mode == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// How games of the mode are compared
+    pub fn ranking(self) -> Ranking {
+        match self {
+            GameMode::Marathon | GameMode::Classic | GameMode::Arcade => Ranking::Score,
+            GameMode::Sprint => Ranking::Time,
+        }
+    }
+
+    /// The lines to clear to finish a game, or [`None`] if it lasts until the player tops out
+    pub fn line_goal(self) -> Option<u32> {
+        match self {
+            GameMode::Sprint => Some(40),
+            GameMode::Marathon | GameMode::Classic | GameMode::Arcade => None,
+        }
+    }
+}
+
+/// What makes one game better than another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ranking {
+    /// The highest score wins, for modes played until the player tops out
+    Score,
+    /// The shortest time wins, for sprint modes played until a goal is reached
+    Time,
+}
+
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            GameMode::Marathon => "Marathon",
+            GameMode::Sprint => "Sprint",
+            GameMode::Classic => "Classic",
+            GameMode::Arcade => "Arcade",
+        })
+    }
+}
+
+/// The rules a game is played with
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Rules {
+    /// The mode the rules started from
+    pub mode: GameMode,
+    /// How long a grounded Tetrimino waits before locking
+    pub lock_delay: Duration,
+    /// Number of times moving or rotating can restart the lock delay
+    ///
+    /// The count is refreshed when the Tetrimino reaches a new lowest row
+    pub max_lock_resets: u32,
+    /// End the game when a Tetrimino locks partly above the visible matrix
+    ///
+    /// Locking entirely above the visible matrix always ends the game
+    pub partial_lock_out: bool,
+    /// How the sequence of Tetriminos is generated
+    pub randomizer: RandomizerKind,
+    /// How Tetriminos look and turn
+    pub rotation_system: RotationSystemKind,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::from(GameMode::default())
+    }
+}
+
+impl From<GameMode> for Rules {
+    fn from(mode: GameMode) -> Self {
+        let guideline = Self {
+            mode,
+            lock_delay: Duration::from_millis(500),
+            max_lock_resets: 15,
+            partial_lock_out: false,
+            randomizer: RandomizerKind::SevenBag,
+            rotation_system: RotationSystemKind::Srs,
+        };
+
+        match mode {
+            GameMode::Marathon | GameMode::Sprint => guideline,
+            GameMode::Classic => Self {
+                max_lock_resets: 0,
+                partial_lock_out: true,
+                randomizer: RandomizerKind::Nes,
+                rotation_system: RotationSystemKind::Nrs,
+                ..guideline
+            },
+            GameMode::Arcade => Self {
+                max_lock_resets: 0,
+                randomizer: RandomizerKind::Tgm,
+                rotation_system: RotationSystemKind::Ars,
+                ..guideline
+            },
+        }
+    }
+}
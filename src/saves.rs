@@ -0,0 +1,123 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use console_tetris::game::GameState;
+
+/// Where a game left unfinished is kept until it is continued
+pub fn path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("console_tetris").join("save.toml"))
+}
+
+/// Load the saved game, if there is one
+///
+/// A save that can't be read is set aside, see [`set_aside`]
+pub fn load() -> Result<Option<GameState>, io::Error> {
+    let Some(path) = path() else {
+        return Ok(None);
+    };
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    toml::from_str(&contents)
+        .map(Some)
+        .map_err(|err| set_aside(&path, err))
+}
+
+/// Save `gamestate` to be continued on the next launch, replacing any saved game
+pub fn save(gamestate: &GameState) -> Result<(), io::Error> {
+    let Some(path) = path() else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no data directory"));
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let contents = toml::to_string(gamestate)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    fs::write(path, contents)
+}
+
+/// Delete the saved game, so it is only continued once
+pub fn remove() -> Result<(), io::Error> {
+    let Some(path) = path() else {
+        return Ok(());
+    };
+
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Move the unreadable file at `path` out of the way, so it is neither loaded
+/// again nor overwritten by the next save
+///
+/// Returns an error describing why it couldn't be read and where it went
+pub fn set_aside(path: &Path, err: impl fmt::Display) -> io::Error {
+    let mut bad = path.as_os_str().to_owned();
+    bad.push(".bad");
+    let bad = PathBuf::from(bad);
+
+    let message = match fs::rename(path, &bad) {
+        Ok(()) => format!("{} moved to {}: {err}", path.display(), bad.display()),
+        Err(_) => format!("{}: {err}", path.display()),
+    };
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use console_tetris::{
+        game::Movement,
+        replay::Input,
+        rules::{GameMode, Rules},
+    };
+
+    use super::*;
+
+    #[test]
+    fn save_round_trip() {
+        for mode in GameMode::ALL {
+            let mut gamestate = GameState::new(Rules::from(mode), 12_345);
+            for _ in 0..100 {
+                gamestate.step();
+            }
+            gamestate.input(Input::Move(Movement::Drop));
+            gamestate.input(Input::Hold);
+
+            let contents = toml::to_string(&gamestate).expect("game can be saved");
+            let loaded: GameState = toml::from_str(&contents).expect("save can be loaded");
+            assert_eq!(loaded, gamestate);
+        }
+    }
+
+    #[test]
+    fn unreadable_files_are_set_aside() {
+        let dir = env::temp_dir().join(format!("console_tetris-saves-{}", process::id()));
+        fs::create_dir_all(&dir).expect("temporary directory can be created");
+        let path = dir.join("save.toml");
+        fs::write(&path, "not a save").expect("save can be written");
+
+        let err = set_aside(&path, "expected a table");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("save.toml.bad"), "{err}");
+        assert!(err.to_string().ends_with("expected a table"), "{err}");
+
+        // the next save starts fresh, and the unreadable one is kept for the player
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(dir.join("save.toml.bad")).expect("save was moved"),
+            "not a save"
+        );
+
+        fs::remove_dir_all(dir).expect("temporary directory can be removed");
+    }
+}
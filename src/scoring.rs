@@ -0,0 +1,334 @@
+use crate::{
+    game::{LockResult, Movement},
+    tetramino::TSpin,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Number of lines required to advance a level
+pub const LINES_PER_LEVEL: u32 = 10;
+
+/// Tracks the score, lines cleared and level of a game
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Scoring {
+    pub score: u32,
+    pub lines: u32,
+    pub level: i32,
+    /// Number of Tetriminos locked
+    pub pieces: u32,
+    /// Consecutive line clearing Tetriminos after the first, [`None`] outside a combo
+    pub combo: Option<u32>,
+    /// Whether the last line clear was difficult (a Tetris or a T-spin)
+    pub back_to_back: bool,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Self {
+            score: 0,
+            lines: 0,
+            level: 1,
+            pieces: 0,
+            combo: None,
+            back_to_back: false,
+        }
+    }
+}
+
+/// A lock and the bonuses it earned, for the HUD
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Callout {
+    pub result: LockResult,
+    /// The clear continued a back-to-back chain
+    pub back_to_back: bool,
+    /// The combo count after the clear
+    pub combo: u32,
+}
+
+impl Callout {
+    /// The lines of text to show for the lock, empty if it is not worth calling out
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![];
+        if self.back_to_back {
+            lines.push("Back-to-Back".to_owned());
+        }
+        if let Some(name) = self.result.name() {
+            lines.push(name);
+        }
+        if self.combo > 0 {
+            lines.push(format!("{} Combo", self.combo));
+        }
+        if self.result.perfect_clear {
+            lines.push("Perfect Clear".to_owned());
+        }
+        lines
+    }
+}
+
+impl Scoring {
+    /// Award points for a locked Tetrimino at the current level
+    ///
+    /// Difficult clears continuing a back-to-back chain are worth 1.5 times as much,
+    /// combos and perfect clears earn a bonus on top
+    ///
+    /// Advances the level every [`LINES_PER_LEVEL`] lines
+    pub fn add_lock(&mut self, result: LockResult) -> Callout {
+        let level = self.level as u32;
+        let lines = result.lines_cleared;
+
+        let mut points = match (result.t_spin, lines) {
+            (None, 0) => 0,
+            (None, 1) => 100,
+            (None, 2) => 300,
+            (None, 3) => 500,
+            (None, _) => 800,
+            (Some(TSpin::Mini), 0) => 100,
+            (Some(TSpin::Mini), 1) => 200,
+            (Some(TSpin::Mini), _) => 400,
+            (Some(TSpin::Full), 0) => 400,
+            (Some(TSpin::Full), 1) => 800,
+            (Some(TSpin::Full), 2) => 1200,
+            (Some(TSpin::Full), _) => 1600,
+        } * level;
+
+        // T-spins without lines neither start nor break a back-to-back chain
+        let difficult = lines == 4 || result.t_spin.is_some();
+        let back_to_back = lines > 0 && difficult && self.back_to_back;
+        if back_to_back {
+            points = points * 3 / 2;
+        }
+        if lines > 0 {
+            self.back_to_back = difficult;
+        }
+
+        self.combo = match (lines, self.combo) {
+            (0, _) => None,
+            (_, None) => Some(0),
+            (_, Some(combo)) => Some(combo + 1),
+        };
+        let combo = self.combo.unwrap_or(0);
+        points += 50 * combo * level;
+
+        if result.perfect_clear {
+            points += match lines {
+                1 => 800,
+                2 => 1200,
+                3 => 1800,
+                _ if back_to_back => 3200,
+                _ => 2000,
+            } * level;
+        }
+
+        self.score += points;
+        self.lines += lines as u32;
+        self.level = self.level.max((self.lines / LINES_PER_LEVEL) as i32 + 1);
+
+        Callout {
+            result,
+            back_to_back,
+            combo,
+        }
+    }
+
+    /// Award points for a drop of `cells` cells
+    ///
+    /// Soft drops ([`Movement::Down`]) are worth 1 point per cell,
+    /// hard drops ([`Movement::Drop`]) are worth 2 points per cell
+    pub fn add_drop(&mut self, movement: Movement, cells: u32) {
+        self.score += match movement {
+            Movement::Down => cells,
+            Movement::Drop => cells * 2,
+            _ => 0,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A lock clearing `lines` lines without a T-spin
+    fn clear(lines: usize) -> LockResult {
+        LockResult {
+            lines_cleared: lines,
+            t_spin: None,
+            perfect_clear: false,
+        }
+    }
+
+    /// A lock clearing `lines` lines with a `t_spin`
+    fn t_spin(t_spin: TSpin, lines: usize) -> LockResult {
+        LockResult {
+            t_spin: Some(t_spin),
+            ..clear(lines)
+        }
+    }
+
+    /// A lock clearing `lines` lines and leaving the matrix empty
+    fn perfect_clear(lines: usize) -> LockResult {
+        LockResult {
+            perfect_clear: true,
+            ..clear(lines)
+        }
+    }
+
+    /// The points `result` adds to `scoring`
+    fn gain(scoring: &mut Scoring, result: LockResult) -> u32 {
+        let before = scoring.score;
+        scoring.add_lock(result);
+        scoring.score - before
+    }
+
+    /// Scoring at `level` with nothing scored yet
+    fn at_level(level: i32) -> Scoring {
+        Scoring {
+            level,
+            ..Scoring::default()
+        }
+    }
+
+    /// The points a single `result` earns from a fresh game at `level`
+    fn points(level: i32, result: LockResult) -> u32 {
+        let mut scoring = at_level(level);
+        scoring.add_lock(result);
+        scoring.score
+    }
+
+    #[test]
+    fn line_clears_score_by_level() {
+        for level in [1, 2, 7] {
+            let level_points = level as u32;
+            assert_eq!(points(level, clear(0)), 0);
+            assert_eq!(points(level, clear(1)), 100 * level_points);
+            assert_eq!(points(level, clear(2)), 300 * level_points);
+            assert_eq!(points(level, clear(3)), 500 * level_points);
+            assert_eq!(points(level, clear(4)), 800 * level_points);
+        }
+    }
+
+    #[test]
+    fn drops_score_per_cell() {
+        let mut scoring = Scoring::default();
+        scoring.add_drop(Movement::Down, 3);
+        assert_eq!(scoring.score, 3);
+        scoring.add_drop(Movement::Drop, 5);
+        assert_eq!(scoring.score, 13);
+        // neither depends on the level
+        let mut scoring = at_level(9);
+        scoring.add_drop(Movement::Drop, 5);
+        assert_eq!(scoring.score, 10);
+        // sideways moves and rotations are worth nothing
+        scoring.add_drop(Movement::Left, 4);
+        assert_eq!(scoring.score, 10);
+    }
+
+    #[test]
+    fn level_advances_every_ten_lines() {
+        let mut scoring = Scoring::default();
+        for _ in 0..2 {
+            scoring.add_lock(clear(4));
+        }
+        assert_eq!((scoring.lines, scoring.level), (8, 1));
+        scoring.add_lock(clear(2));
+        assert_eq!((scoring.lines, scoring.level), (10, 2));
+        for _ in 0..3 {
+            scoring.add_lock(clear(3));
+        }
+        assert_eq!((scoring.lines, scoring.level), (19, 2));
+        scoring.add_lock(clear(1));
+        assert_eq!((scoring.lines, scoring.level), (20, 3));
+
+        // a level picked at the start is kept until the lines catch up
+        let mut scoring = at_level(5);
+        scoring.add_lock(clear(4));
+        assert_eq!(scoring.level, 5);
+    }
+
+    #[test]
+    fn t_spins_score_by_level() {
+        for level in [1, 4] {
+            let level_points = level as u32;
+            assert_eq!(points(level, t_spin(TSpin::Full, 0)), 400 * level_points);
+            assert_eq!(points(level, t_spin(TSpin::Full, 1)), 800 * level_points);
+            assert_eq!(points(level, t_spin(TSpin::Full, 2)), 1200 * level_points);
+            assert_eq!(points(level, t_spin(TSpin::Full, 3)), 1600 * level_points);
+            assert_eq!(points(level, t_spin(TSpin::Mini, 0)), 100 * level_points);
+            assert_eq!(points(level, t_spin(TSpin::Mini, 1)), 200 * level_points);
+            assert_eq!(points(level, t_spin(TSpin::Mini, 2)), 400 * level_points);
+        }
+    }
+
+    #[test]
+    fn back_to_back_chains_difficult_clears() {
+        let mut scoring = Scoring::default();
+
+        // the first difficult clear starts the chain without a bonus
+        assert_eq!(gain(&mut scoring, clear(4)), 800);
+        assert!(scoring.back_to_back);
+        // locks without lines in between break the combo but not the chain
+        gain(&mut scoring, clear(0));
+
+        let callout = scoring.add_lock(t_spin(TSpin::Full, 1));
+        assert!(callout.back_to_back);
+        assert_eq!(scoring.score, 800 + 1200);
+        gain(&mut scoring, clear(0));
+        assert_eq!(gain(&mut scoring, t_spin(TSpin::Mini, 1)), 300);
+        gain(&mut scoring, clear(0));
+
+        // a T-spin without lines neither earns the bonus nor breaks the chain
+        let callout = scoring.add_lock(t_spin(TSpin::Full, 0));
+        assert!(!callout.back_to_back);
+        assert!(scoring.back_to_back);
+        assert_eq!(gain(&mut scoring, clear(4)), 1200);
+        gain(&mut scoring, clear(0));
+
+        // any other line clear breaks it
+        let callout = scoring.add_lock(clear(3));
+        assert!(!callout.back_to_back);
+        assert!(!scoring.back_to_back);
+        gain(&mut scoring, clear(0));
+        let callout = scoring.add_lock(clear(4));
+        assert!(!callout.back_to_back);
+        assert!(scoring.back_to_back);
+    }
+
+    #[test]
+    fn combos_count_consecutive_line_clears() {
+        let mut scoring = at_level(3);
+
+        assert_eq!(gain(&mut scoring, clear(1)), 300);
+        assert_eq!(scoring.combo, Some(0));
+        let callout = scoring.add_lock(clear(1));
+        assert_eq!(callout.combo, 1);
+        assert_eq!(scoring.score, 300 + 300 + 150);
+        assert_eq!(gain(&mut scoring, clear(2)), 900 + 300);
+        assert_eq!(scoring.combo, Some(2));
+
+        // a lock without lines ends the combo
+        assert_eq!(gain(&mut scoring, clear(0)), 0);
+        assert_eq!(scoring.combo, None);
+        assert_eq!(gain(&mut scoring, clear(1)), 300);
+        assert_eq!(scoring.combo, Some(0));
+    }
+
+    #[test]
+    fn perfect_clears_earn_a_bonus() {
+        for level in [1, 2] {
+            let level_points = level as u32;
+            assert_eq!(points(level, perfect_clear(1)), (100 + 800) * level_points);
+            assert_eq!(points(level, perfect_clear(2)), (300 + 1200) * level_points);
+            assert_eq!(points(level, perfect_clear(3)), (500 + 1800) * level_points);
+            assert_eq!(points(level, perfect_clear(4)), (800 + 2000) * level_points);
+        }
+
+        // a back-to-back Tetris perfect clear is worth more
+        let mut scoring = Scoring::default();
+        gain(&mut scoring, clear(4));
+        gain(&mut scoring, clear(0));
+        let callout = scoring.add_lock(perfect_clear(4));
+        assert!(callout.back_to_back);
+        assert_eq!(scoring.score, 800 + 1200 + 3200);
+    }
+}
@@ -0,0 +1,143 @@
+use std::{fmt, time::Duration};
+
+use console_tetris::{
+    game::NEXT_QUEUE_LENGTH,
+    randomizer::RandomizerKind,
+    rotation::RotationSystemKind,
+    rules::{GameMode, Rules},
+};
+
+/// The DAS values the settings menu cycles through, in milliseconds
+const DAS_CHOICES: [u64; 8] = [50, 83, 100, 117, 133, 167, 200, 250];
+/// The ARR values the settings menu cycles through, in milliseconds
+const ARR_CHOICES: [u64; 6] = [0, 10, 17, 33, 50, 83];
+/// The soft drop factors the settings menu cycles through
+const SOFT_DROP_CHOICES: [u32; 5] = [5, 10, 20, 40, 80];
+
+/// A side of the matrix
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    #[default]
+    Right,
+}
+
+impl Side {
+    /// The other side
+    pub fn flipped(self) -> Self {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Side::Left => "Left",
+            Side::Right => "Right",
+        })
+    }
+}
+
+/// Player preferences that outlive a single game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// Draw a ghost of the current Tetrimino where it will land
+    pub ghost_piece: bool,
+    /// Delayed Auto Shift, how long a direction is held before it starts repeating
+    pub das: Duration,
+    /// Auto Repeat Rate, the time between repeated shifts
+    ///
+    /// Zero moves the Tetrimino straight to the wall
+    pub arr: Duration,
+    /// How many times faster than gravity soft drop is
+    pub soft_drop_factor: u32,
+    /// How many upcoming Tetriminos the next queue shows
+    pub next_previews: usize,
+    /// The side of the matrix the next queue is drawn on, hold goes on the other
+    pub next_queue_side: Side,
+    /// The randomizer to play every mode with, the mode's own if [`None`]
+    pub randomizer: Option<RandomizerKind>,
+    /// The rotation system to play every mode with, the mode's own if [`None`]
+    pub rotation_system: Option<RotationSystemKind>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            ghost_piece: true,
+            das: Duration::from_millis(167),
+            arr: Duration::from_millis(33),
+            soft_drop_factor: 20,
+            next_previews: 6,
+            next_queue_side: Side::Right,
+            randomizer: None,
+            rotation_system: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Switch to the next longer DAS, wrapping around to the shortest
+    pub fn cycle_das(&mut self) {
+        let das = cycle(&DAS_CHOICES, self.das.as_millis() as u64);
+        self.das = Duration::from_millis(das);
+    }
+
+    /// Switch to the next longer ARR, wrapping around to the shortest
+    pub fn cycle_arr(&mut self) {
+        let arr = cycle(&ARR_CHOICES, self.arr.as_millis() as u64);
+        self.arr = Duration::from_millis(arr);
+    }
+
+    /// Switch to the next larger soft drop factor, wrapping around to the smallest
+    pub fn cycle_soft_drop_factor(&mut self) {
+        self.soft_drop_factor = cycle(&SOFT_DROP_CHOICES, self.soft_drop_factor);
+    }
+
+    /// Show one more upcoming Tetrimino, wrapping around to none
+    pub fn cycle_next_previews(&mut self) {
+        self.next_previews = (self.next_previews + 1) % (NEXT_QUEUE_LENGTH + 1);
+    }
+
+    /// Switch to the next randomizer, going back to the mode's own after the last
+    pub fn cycle_randomizer(&mut self) {
+        self.randomizer = cycle_override(&RandomizerKind::ALL, self.randomizer);
+    }
+
+    /// Switch to the next rotation system, going back to the mode's own after the last
+    pub fn cycle_rotation_system(&mut self) {
+        self.rotation_system = cycle_override(&RotationSystemKind::ALL, self.rotation_system);
+    }
+
+    /// The [`Rules`] of `mode` with the chosen randomizer and rotation system
+    pub fn rules(&self, mode: GameMode) -> Rules {
+        let mut rules = Rules::from(mode);
+        if let Some(randomizer) = self.randomizer {
+            rules.randomizer = randomizer;
+        }
+        if let Some(rotation_system) = self.rotation_system {
+            rules.rotation_system = rotation_system;
+        }
+        rules
+    }
+}
+
+/// The choice after `current`, [`None`] after the last and the first after [`None`]
+fn cycle_override<T: PartialEq + Copy>(choices: &[T], current: Option<T>) -> Option<T> {
+    match current.and_then(|current| choices.iter().position(|choice| *choice == current)) {
+        Some(index) => choices.get(index + 1).copied(),
+        None => choices.first().copied(),
+    }
+}
+
+/// The first of the sorted `choices` after `current`, or the first one if there is none
+fn cycle<T: PartialOrd + Copy>(choices: &[T], current: T) -> T {
+    choices
+        .iter()
+        .copied()
+        .find(|choice| *choice > current)
+        .unwrap_or(choices[0])
+}
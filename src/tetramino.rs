@@ -1,21 +1,15 @@
 use crate::{
-    game_handler::RotationDirection,
-    matrix::{get_spawn_point, GridRotation, Matrix, MinoGrid},
+    matrix::{get_spawn_point, Matrix, MinoGrid},
     position_outside_bounds,
+    rotation::{RotationDirection, RotationSystemKind},
 };
 use grid::grid;
 use rand::{distributions::Standard, prelude::Distribution};
-use ratatui::style::Color;
-
-const I_COLOR: Color = Color::Indexed(51);
-const J_COLOR: Color = Color::Indexed(33);
-const L_COLOR: Color = Color::Indexed(208);
-const O_COLOR: Color = Color::Indexed(226);
-const S_COLOR: Color = Color::Indexed(40);
-const T_COLOR: Color = Color::Indexed(128);
-const Z_COLOR: Color = Color::Indexed(160);
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Facing {
     North,
     East,
@@ -38,11 +32,26 @@ impl Facing {
                 Self::South => Self::East,
                 Self::West => Self::South,
             },
+            RotationDirection::Half => match self {
+                Self::North => Self::South,
+                Self::East => Self::West,
+                Self::South => Self::North,
+                Self::West => Self::East,
+            },
         }
     }
 }
 
+/// A T-spin detected when a T Tetrimino locks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TSpin {
+    Mini,
+    Full,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TetriminoType {
     O,
     I,
@@ -54,9 +63,40 @@ pub enum TetriminoType {
 }
 
 impl TetriminoType {
-    /// Returns a [`Vec`] of offsets for the type of Tetrimino
+    pub const ALL: [TetriminoType; 7] = [
+        TetriminoType::O,
+        TetriminoType::I,
+        TetriminoType::T,
+        TetriminoType::L,
+        TetriminoType::J,
+        TetriminoType::S,
+        TetriminoType::Z,
+    ];
+
+    /// The letter the Tetrimino is named after
+    pub fn letter(self) -> char {
+        match self {
+            TetriminoType::O => 'O',
+            TetriminoType::I => 'I',
+            TetriminoType::T => 'T',
+            TetriminoType::L => 'L',
+            TetriminoType::J => 'J',
+            TetriminoType::S => 'S',
+            TetriminoType::Z => 'Z',
+        }
+    }
+
+    /// The Tetrimino named `letter`
+    pub fn from_letter(letter: char) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|tetrimino_type| tetrimino_type.letter() == letter)
+    }
+
+    /// Returns a [`Vec`] of SRS offsets for the type of Tetrimino
     ///
-    /// Offsets should be tried sequentially
+    /// Offsets should be tried sequentially.
+    /// Half turns use the SRS+ 180 kicks, on top of the offset of the true rotation
     pub fn get_offset_data(
         &self,
         origin_rotation: Facing,
@@ -78,12 +118,30 @@ impl TetriminoType {
             Self::O => grid![[(0, 0)][(0, -1)][(-1, -1)][(-1, 0)]],
         };
 
-        offset_table
+        let mut offsets = offset_table
             .iter_row(origin_rotation as usize)
             .zip(offset_table.iter_row(target_rotation as usize))
             .map(|((origin_x, origin_y), (target_x, target_y))| {
                 (origin_x - target_x, origin_y - target_y)
-            })
+            });
+
+        if origin_rotation.rotated(RotationDirection::Half) != target_rotation {
+            return offsets.collect();
+        }
+
+        // the offset tables only cover quarter turns, two of them in a row
+        // would kick twice and end up in places a half turn can't reach
+        let half_turn_table = grid![
+            [(0, 0), ( 0,  1), ( 1,  1), (-1,  1), ( 1, 0), (-1, 0)]
+            [(0, 0), ( 1,  0), ( 1,  2), ( 1,  1), ( 0, 2), ( 0, 1)]
+            [(0, 0), ( 0, -1), (-1, -1), ( 1, -1), (-1, 0), ( 1, 0)]
+            [(0, 0), (-1,  0), (-1,  2), (-1,  1), ( 0, 2), ( 0, 1)]
+        ];
+        let (x, y) = offsets.next().unwrap_or_default();
+
+        half_turn_table
+            .iter_row(origin_rotation as usize)
+            .map(|(kick_x, kick_y)| (x + kick_x, y + kick_y))
             .collect()
     }
 }
@@ -102,25 +160,37 @@ impl Distribution<TetriminoType> for Standard {
     }
 }
 
+/// A Tetrimino shown outside the matrix, in the hold slot or the next queue
+///
+/// The minos are moved up and left until they touch the top-left corner, rows count down
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TetriminoPreview {
-    /// the grid of minos making up the Tetrimino
-    minos: Matrix,
-    /// the index of the preview
-    index: usize,
+    minos: Vec<Mino>,
 }
 
-impl MinoGrid for TetriminoPreview {
-    fn get_minos(&self) -> Vec<Mino> {
+impl TetriminoPreview {
+    /// The number of columns the minos span
+    pub fn width(&self) -> i32 {
         self.minos
-            .get_minos()
             .iter()
-            .map(|mino| Mino {
-                col: mino.col,
-                row: mino.row + 3 * self.index as i32,
-                color: mino.color,
-            })
-            .collect()
+            .map(|mino| mino.col + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The number of rows the minos span
+    pub fn height(&self) -> i32 {
+        self.minos
+            .iter()
+            .map(|mino| mino.row + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl MinoGrid for TetriminoPreview {
+    fn get_minos(&self) -> Vec<Mino> {
+        self.minos.to_owned()
     }
 }
 
@@ -128,10 +198,11 @@ impl MinoGrid for TetriminoPreview {
 pub struct Mino {
     pub col: i32,
     pub row: i32,
-    pub color: Color,
+    pub tetrimino_type: TetriminoType,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Tetrimino {
     // the type of the Tetrimino
     tetrimino_type: TetriminoType,
@@ -141,6 +212,10 @@ pub struct Tetrimino {
     col: i32,
     /// the row of the top-left corner of the bound-box
     row: i32,
+    /// the direction and index of the offset used if the last successful action was a rotation
+    last_rotation: Option<(RotationDirection, usize)>,
+    /// how the Tetrimino looks and turns
+    rotation_system: RotationSystemKind,
 }
 
 impl MinoGrid for Tetrimino {
@@ -151,91 +226,69 @@ impl MinoGrid for Tetrimino {
             .map(|mino| Mino {
                 col: self.col + mino.col,
                 row: self.row - mino.row,
-                color: mino.color,
+                tetrimino_type: mino.tetrimino_type,
             })
             .collect()
     }
 }
 
 impl Tetrimino {
-    /// Create a new Tetrimino
-    pub fn new(tetrimino_type: TetriminoType) -> Tetrimino {
+    /// Create a new Tetrimino at the spawn point
+    pub fn new(tetrimino_type: TetriminoType, rotation_system: RotationSystemKind) -> Tetrimino {
         let (col, row) = get_spawn_point();
+        let system = rotation_system.system();
 
         Tetrimino {
             tetrimino_type,
-            minos: Matrix::from(match tetrimino_type {
-                TetriminoType::O => grid![
-                    [None, None, None, None, None]
-                    [None, None, Some(O_COLOR), Some(O_COLOR), None]
-                    [None, None, Some(O_COLOR), Some(O_COLOR), None]
-                    [None, None, None, None, None]
-                    [None, None, None, None, None]
-                ],
-                TetriminoType::I => grid![
-                    [None, None, None, None, None]
-                    [None, None, None, None, None]
-                    [None, Some(I_COLOR), Some(I_COLOR), Some(I_COLOR), Some(I_COLOR)]
-                    [None, None, None, None, None]
-                    [None, None, None, None, None]
-                ],
-                TetriminoType::T => grid![
-                    [None, None, None, None, None]
-                    [None, None, Some(T_COLOR), None, None]
-                    [None, Some(T_COLOR), Some(T_COLOR), Some(T_COLOR), None]
-                    [None, None, None, None, None]
-                ],
-                TetriminoType::L => grid![
-                    [None, None, None, None, None]
-                    [None, None, None, Some(L_COLOR), None]
-                    [None, Some(L_COLOR), Some(L_COLOR), Some(L_COLOR), None]
-                    [None, None, None, None, None]
-                    [None, None, None, None, None]
-                ],
-                TetriminoType::J => grid![
-                    [None, None, None, None, None]
-                    [None, Some(J_COLOR), None, None, None]
-                    [None, Some(J_COLOR), Some(J_COLOR), Some(J_COLOR), None]
-                    [None, None, None, None, None]
-                    [None, None, None, None, None]
-                ],
-                TetriminoType::S => grid![
-                    [None, None, None, None, None]
-                    [None, None, Some(S_COLOR), Some(S_COLOR), None]
-                    [None, Some(S_COLOR), Some(S_COLOR), None, None]
-                    [None, None, None, None, None]
-                    [None, None, None, None, None]
-                ],
-                TetriminoType::Z => grid![
-                    [None, None, None, None, None]
-                    [None, Some(Z_COLOR), Some(Z_COLOR), None, None]
-                    [None, None, Some(Z_COLOR), Some(Z_COLOR), None]
-                    [None, None, None, None, None]
-                    [None, None, None, None, None]
-                ],
-            }),
+            minos: system.shape(tetrimino_type, system.spawn_facing(tetrimino_type)),
             col,
             row,
+            last_rotation: None,
+            rotation_system,
         }
     }
 
-    /// Return a [`Vec`] of all Tetriminos
-    pub fn all() -> Vec<Tetrimino> {
-        vec![
-            Tetrimino::new(TetriminoType::O),
-            Tetrimino::new(TetriminoType::I),
-            Tetrimino::new(TetriminoType::T),
-            Tetrimino::new(TetriminoType::L),
-            Tetrimino::new(TetriminoType::J),
-            Tetrimino::new(TetriminoType::S),
-            Tetrimino::new(TetriminoType::Z),
-        ]
+    pub fn tetrimino_type(&self) -> TetriminoType {
+        self.tetrimino_type
+    }
+
+    pub fn facing(&self) -> Facing {
+        self.minos.rotation
+    }
+
+    pub fn rotation_system(&self) -> RotationSystemKind {
+        self.rotation_system
     }
 
-    pub fn preview(&self, index: usize) -> TetriminoPreview {
+    /// The `(col, row)` of the top-left corner of the bound-box
+    pub fn position(&self) -> (i32, i32) {
+        (self.col, self.row)
+    }
+
+    /// The row of the lowest mino in the matrix
+    pub fn bottom_row(&self) -> i32 {
+        self.get_minos()
+            .iter()
+            .map(|mino| mino.row)
+            .min()
+            .unwrap_or(self.row)
+    }
+
+    /// The Tetrimino as it is shown in the hold slot and the next queue
+    pub fn preview(&self) -> TetriminoPreview {
+        let minos = self.minos.get_minos();
+        let left = minos.iter().map(|mino| mino.col).min().unwrap_or(0);
+        let top = minos.iter().map(|mino| mino.row).min().unwrap_or(0);
+
         TetriminoPreview {
-            minos: self.minos.clone(),
-            index,
+            minos: minos
+                .into_iter()
+                .map(|mino| Mino {
+                    col: mino.col - left,
+                    row: mino.row - top,
+                    tetrimino_type: mino.tetrimino_type,
+                })
+                .collect(),
         }
     }
 
@@ -249,7 +302,7 @@ impl Tetrimino {
     /// [`None`] indicates the position is valid
     /// If [`Some`], there will always be at least one mino in the [`Vec`]
     ///
-    /// ```
+    /// ```ignore
     /// let mino = (grid_x_position, grid_y_position, mino_color);
     /// ```
     pub fn position_invalid(
@@ -301,33 +354,316 @@ impl Tetrimino {
         // move the piece if valid
         self.col += col;
         self.row += row;
+        self.last_rotation = None;
 
         true
     }
 
-    /// Rotate the Tetrimino
+    /// Rotate the Tetrimino following its [`RotationSystem`](crate::rotation::RotationSystem)
     ///
     /// Does nothing if the position would be invalid after the rotation
     pub fn rotate(&mut self, rotation_direction: RotationDirection, matrix: &Matrix) -> bool {
-        // store the previous state in case rotation is impossible
-        let original_minos = self.minos.to_owned();
-
-        // make the rotated grid
-        self.minos = self.minos.rotated(rotation_direction);
-
-        // Super-Rotation-System uses an offset table to try and place Tetrimino
-        for (x, y) in self
-            .tetrimino_type
-            .get_offset_data(original_minos.rotation, self.minos.rotation)
-        {
-            if self.move_position(x, y, matrix) {
-                // position is okay
+        let system = self.rotation_system.system();
+        let from = self.facing();
+
+        // make the rotated Tetrimino where this one is
+        let mut rotated = self.to_owned();
+        rotated.minos = system.shape(self.tetrimino_type, from.rotated(rotation_direction));
+
+        // the rotation system decides where else to try and place it
+        for (kick, (x, y)) in system.kicks(&rotated, from, matrix).into_iter().enumerate() {
+            if rotated.move_position(x, y, matrix) {
+                // position is okay, remember how it got there for T-spin detection
+                rotated.last_rotation = Some((rotation_direction, kick));
+                *self = rotated;
                 return true;
             }
         }
 
-        // rotation is impossible, revert the state
-        self.minos = original_minos;
+        // rotation is impossible
         false
     }
+
+    /// Check if the Tetrimino is in a T-spin using the 3-corner rule
+    ///
+    /// The last successful action must have been a rotation and at least three of the
+    /// four cells diagonal to the center must be occupied. It is a [`TSpin::Mini`] unless
+    /// both corners on the pointing side are occupied or the last offset of a quarter turn
+    /// (the SRS TST kick) was used.
+    ///
+    /// The center and pointing side come from the minos, so it works with any rotation system
+    pub fn t_spin(&self, matrix: &Matrix) -> Option<TSpin> {
+        if self.tetrimino_type != TetriminoType::T {
+            return None;
+        }
+        let (rotation_direction, kick) = self.last_rotation?;
+
+        // the center of the T is the mino next to the other three
+        let minos = self.get_minos();
+        let is_mino =
+            |col: i32, row: i32| minos.iter().any(|mino| (mino.col, mino.row) == (col, row));
+        let sides = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+        let center = minos.iter().find(|mino| {
+            sides
+                .iter()
+                .filter(|(x, y)| is_mino(mino.col + x, mino.row + y))
+                .count()
+                == 3
+        })?;
+        let (col, row) = (center.col, center.row);
+
+        // the T points to the side with no mino opposite
+        let (point_x, point_y) = sides
+            .into_iter()
+            .find(|(x, y)| is_mino(col + x, row + y) && !is_mino(col - x, row - y))?;
+
+        let occupied = |(x, y): (i32, i32)| {
+            let (col, row) = (col + x, row + y);
+            position_outside_bounds!(col, row)
+                || matrix.get_mino(row as usize, col as usize).is_some()
+        };
+
+        // the corners on either side of the point, then the ones behind
+        let (side_x, side_y) = (point_y, point_x);
+        let front = [
+            (point_x + side_x, point_y + side_y),
+            (point_x - side_x, point_y - side_y),
+        ];
+        let back = [
+            (-point_x + side_x, -point_y + side_y),
+            (-point_x - side_x, -point_y - side_y),
+        ];
+        let front = front.into_iter().filter(|corner| occupied(*corner)).count();
+        let back = back.into_iter().filter(|corner| occupied(*corner)).count();
+
+        if front + back < 3 {
+            None
+        } else if front == 2 || (rotation_direction != RotationDirection::Half && kick == 4) {
+            Some(TSpin::Full)
+        } else {
+            Some(TSpin::Mini)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::{MATRIX_BUFFER_HEIGHT, MATRIX_HEIGHT, MATRIX_WIDTH};
+
+    /// An empty matrix with `(col, row)` filled
+    fn matrix_with(filled: &[(i32, i32)]) -> Matrix {
+        let mut matrix = Matrix::new(
+            (MATRIX_HEIGHT + MATRIX_BUFFER_HEIGHT).into(),
+            MATRIX_WIDTH.into(),
+            Facing::North,
+        );
+        for &(col, row) in filled {
+            matrix.set_mino(Mino {
+                col,
+                row,
+                tetrimino_type: TetriminoType::O,
+            });
+        }
+        matrix
+    }
+
+    /// An SRS `tetrimino_type` facing `facing` with the center of its bound-box at
+    /// `(col, row)`
+    fn srs_at(tetrimino_type: TetriminoType, facing: Facing, (col, row): (i32, i32)) -> Tetrimino {
+        let system = RotationSystemKind::Srs.system();
+        Tetrimino {
+            minos: system.shape(tetrimino_type, facing),
+            col: col - 2,
+            row: row + 2,
+            ..Tetrimino::new(tetrimino_type, RotationSystemKind::Srs)
+        }
+    }
+
+    /// An SRS T facing `facing` with its center at `(col, row)`
+    fn t_at(facing: Facing, position: (i32, i32)) -> Tetrimino {
+        srs_at(TetriminoType::T, facing, position)
+    }
+
+    /// The sorted `(col, row)` of every mino of `tetrimino`
+    fn cells(tetrimino: &Tetrimino) -> Vec<(i32, i32)> {
+        let mut cells: Vec<(i32, i32)> = tetrimino
+            .get_minos()
+            .iter()
+            .map(|mino| (mino.col, mino.row))
+            .collect();
+        cells.sort();
+        cells
+    }
+
+    /// `tetrimino` after two clockwise turns in an empty matrix
+    fn turned_twice(tetrimino: &Tetrimino) -> Tetrimino {
+        let matrix = matrix_with(&[]);
+        let mut turned = tetrimino.to_owned();
+        assert!(turned.rotate(RotationDirection::Clockwise, &matrix));
+        assert!(turned.rotate(RotationDirection::Clockwise, &matrix));
+        turned
+    }
+
+    /// A well on the bottom row with both corners of the T at the bottom filled but
+    /// only one at the top
+    fn mini_slot() -> Matrix {
+        let mut filled: Vec<(i32, i32)> = (0..10)
+            .filter(|col| *col != 1)
+            .map(|col| (col, 0))
+            .collect();
+        filled.push((0, 2));
+        matrix_with(&filled)
+    }
+
+    #[test]
+    fn t_spin_double() {
+        // a slot two rows deep with an overhang on the left
+        let mut filled: Vec<(i32, i32)> = (0..10)
+            .filter(|col| *col != 4)
+            .map(|col| (col, 0))
+            .collect();
+        filled.extend(
+            (0..10)
+                .filter(|col| !(3..=5).contains(col))
+                .map(|col| (col, 1)),
+        );
+        filled.push((3, 2));
+        let mut matrix = matrix_with(&filled);
+
+        let mut t = t_at(Facing::East, (4, 1));
+        assert!(t.rotate(RotationDirection::Clockwise, &matrix));
+        assert_eq!(t.facing(), Facing::South);
+        assert_eq!(t.t_spin(&matrix), Some(TSpin::Full));
+
+        for mino in t.get_minos() {
+            matrix.set_mino(mino);
+        }
+        assert_eq!(matrix.clear_lines(), 2);
+    }
+
+    #[test]
+    fn t_spin_mini() {
+        let matrix = mini_slot();
+
+        let mut t = t_at(Facing::East, (1, 1));
+        assert!(t.rotate(RotationDirection::Counterclockwise, &matrix));
+        assert_eq!(t.facing(), Facing::North);
+        assert_eq!(t.position(), (-1, 3));
+        assert_eq!(t.t_spin(&matrix), Some(TSpin::Mini));
+    }
+
+    #[test]
+    fn t_spin_mini_upgraded_by_the_last_kick() {
+        // the first four kicks are blocked, the last one drops the T two rows
+        // into a spot that only has one corner filled in front
+        let matrix = matrix_with(&[(0, 0), (0, 2), (2, 2), (1, 4)]);
+
+        let mut t = t_at(Facing::North, (2, 3));
+        assert!(t.rotate(RotationDirection::Clockwise, &matrix));
+        assert_eq!(t.facing(), Facing::East);
+        assert_eq!(t.position(), (-1, 3));
+        assert_eq!(t.last_rotation, Some((RotationDirection::Clockwise, 4)));
+        assert_eq!(t.t_spin(&matrix), Some(TSpin::Full));
+
+        // the same spot reached without the last kick is only a mini
+        t.last_rotation = Some((RotationDirection::Clockwise, 0));
+        assert_eq!(t.t_spin(&matrix), Some(TSpin::Mini));
+    }
+
+    #[test]
+    fn no_t_spin_after_a_move() {
+        let matrix = mini_slot();
+
+        let mut t = t_at(Facing::East, (1, 1));
+        assert!(t.rotate(RotationDirection::Counterclockwise, &matrix));
+        assert!(t.move_position(1, 0, &matrix));
+        assert!(t.move_position(-1, 0, &matrix));
+        assert_eq!(t.t_spin(&matrix), None);
+    }
+
+    const FACINGS: [Facing; 4] = [Facing::North, Facing::East, Facing::South, Facing::West];
+
+    #[test]
+    fn half_turn_in_the_open_lands_like_two_quarter_turns() {
+        let matrix = matrix_with(&[]);
+
+        for tetrimino_type in TetriminoType::ALL {
+            for facing in FACINGS {
+                let tetrimino = srs_at(tetrimino_type, facing, (4, 10));
+                let mut turned = tetrimino.to_owned();
+                assert!(turned.rotate(RotationDirection::Half, &matrix));
+
+                let expected = turned_twice(&tetrimino);
+                assert_eq!(turned.facing(), expected.facing());
+                assert_eq!(
+                    cells(&turned),
+                    cells(&expected),
+                    "{tetrimino_type:?} from {facing:?}"
+                );
+                assert_eq!(turned.last_rotation, Some((RotationDirection::Half, 0)));
+            }
+        }
+    }
+
+    #[test]
+    fn half_turn_on_the_floor_kicks_up_a_row() {
+        let matrix = matrix_with(&[]);
+
+        for tetrimino_type in TetriminoType::ALL {
+            if tetrimino_type == TetriminoType::O {
+                continue;
+            }
+
+            let in_the_air = srs_at(tetrimino_type, Facing::North, (4, 10));
+            let mut turned = in_the_air.to_owned();
+            while turned.move_position(0, -1, &matrix) {}
+            assert!(turned.rotate(RotationDirection::Half, &matrix));
+
+            // turning in place would go through the floor, so it lands like the same
+            // turn in the air moved down to rest on the floor
+            let in_the_air = cells(&turned_twice(&in_the_air));
+            let lowest = in_the_air.iter().map(|(_, row)| *row).min().unwrap();
+            let expected: Vec<(i32, i32)> = in_the_air
+                .into_iter()
+                .map(|(col, row)| (col, row - lowest))
+                .collect();
+            assert_eq!(cells(&turned), expected, "{tetrimino_type:?}");
+            assert_eq!(turned.last_rotation, Some((RotationDirection::Half, 1)));
+        }
+
+        let mut t = t_at(Facing::North, (4, 0));
+        assert!(t.rotate(RotationDirection::Half, &matrix));
+        assert_eq!(cells(&t), vec![(3, 1), (4, 0), (4, 1), (5, 1)]);
+    }
+
+    #[test]
+    fn half_turn_kicks_off_the_wall() {
+        let matrix = matrix_with(&[]);
+
+        // a T against the left wall pointing right turns to point into the wall
+        let mut t = t_at(Facing::East, (0, 10));
+        assert!(t.rotate(RotationDirection::Half, &matrix));
+        assert_eq!(t.facing(), Facing::West);
+        assert_eq!(t.last_rotation, Some((RotationDirection::Half, 1)));
+        assert_eq!(cells(&t), vec![(0, 10), (1, 9), (1, 10), (1, 11)]);
+    }
+
+    #[test]
+    fn half_turn_kicks_aside_under_an_overhang() {
+        // a T pointing down into a one wide well with a block over it can't turn in
+        // place or further down, the first kick that fits moves it to the left
+        let mut filled: Vec<(i32, i32)> = (0..10)
+            .filter(|col| *col != 4)
+            .map(|col| (col, 0))
+            .collect();
+        filled.push((4, 2));
+        let matrix = matrix_with(&filled);
+
+        let mut t = t_at(Facing::South, (4, 1));
+        assert!(t.rotate(RotationDirection::Half, &matrix));
+        assert_eq!(t.facing(), Facing::North);
+        assert_eq!(t.last_rotation, Some((RotationDirection::Half, 4)));
+        assert_eq!(t.position(), t_at(Facing::North, (3, 1)).position());
+    }
 }